@@ -3,11 +3,11 @@ use std::{
     rc::Rc,
 };
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     commands::{Literal, Procedure, Value},
-    heap::Heap,
+    heap::{Heap, Slot},
     tokens::{
         AssignmentType, CalculationOperation, CommandType, LogicalOperation, QueryType, TokenType,
     },
@@ -17,6 +17,7 @@ pub trait Executable {
 }
 impl Executable for Statement {
     fn execute(&self, heap: &mut Heap) {
+        heap.enter_statement(self);
         match self {
             Statement::CallExpression(call) => {
                 call.call(heap);
@@ -38,18 +39,15 @@ impl Executable for CallExpression {
 
 impl Executable for AssignmentExpression {
     fn execute(&self, heap: &mut Heap) {
+        let slot = self.slot.unwrap_or_else(|| heap.intern(&self.left));
         let assiganble = heap.declare_variable(&self.operation).clone();
-        assiganble.declare(&self.left, &self.right, heap);
+        assiganble.declare(slot, &self.right, heap);
     }
 }
 
 impl Executable for IfStatement {
     fn execute(&self, heap: &mut Heap) {
-        let test = self
-            .test
-            .expect_literal_r(heap)
-            .expect("Literal is expected");
-        if test.expect_bool().expect("Bool value is expected") {
+        if self.holds(heap) {
             for statement in &self.consequent {
                 statement.execute(heap);
             }
@@ -75,12 +73,19 @@ impl Executable for WhileStatement {
 impl Executable for ProcedureDeclaration {
     fn execute(&self, heap: &mut Heap) {
         let body: Vec<Statement> = self.body.to_owned();
-        let arguments = self.arguments.to_owned();
-        heap.declare_procedure(self.name.clone(), Rc::new(Procedure::new(arguments, body)));
+        let arguments = self
+            .arguments
+            .iter()
+            .map(|argument| heap.intern(argument))
+            .collect();
+        heap.declare_procedure(
+            self.name.clone(),
+            Rc::new(Procedure::new(self.name.clone(), arguments, body)),
+        );
     }
 }
 
-#[derive(Clone, Serialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Statement {
     CallExpression(CallExpression),
     AssignmentExpression(AssignmentExpression),
@@ -89,6 +94,26 @@ pub enum Statement {
     ProcedureDeclaration(ProcedureDeclaration),
 }
 impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::CallExpression(call) => call.span,
+            Statement::AssignmentExpression(assign) => assign.span,
+            Statement::If(if_statement) => if_statement.span,
+            Statement::While(while_statement) => while_statement.span,
+            Statement::ProcedureDeclaration(procedure_declaration) => procedure_declaration.span,
+        }
+    }
+    pub fn set_span(&mut self, span: Span) {
+        match self {
+            Statement::CallExpression(call) => call.span = span,
+            Statement::AssignmentExpression(assign) => assign.span = span,
+            Statement::If(if_statement) => if_statement.span = span,
+            Statement::While(while_statement) => while_statement.span = span,
+            Statement::ProcedureDeclaration(procedure_declaration) => {
+                procedure_declaration.span = span
+            }
+        }
+    }
     pub fn expect_callexpression(&self) -> Option<CallExpression> {
         match self {
             Statement::CallExpression(callexpression) => Some(callexpression.clone()),
@@ -97,11 +122,15 @@ impl Statement {
     }
 }
 
-#[derive(Clone, Serialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct AssignmentExpression {
     operation: AssignmentType,
     left: String,
     right: Value,
+    #[serde(skip)]
+    slot: Option<Slot>,
+    #[serde(default)]
+    span: Span,
 }
 impl AssignmentExpression {
     pub fn new(operation: AssignmentType, left: String, right: Value) -> AssignmentExpression {
@@ -109,15 +138,19 @@ impl AssignmentExpression {
             operation,
             left,
             right,
+            slot: None,
+            span: Span::default(),
         }
     }
 }
 
-#[derive(Clone, Serialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ProcedureDeclaration {
     name: String,
     arguments: Vec<String>,
     body: Vec<Statement>,
+    #[serde(default)]
+    span: Span,
 }
 
 impl ProcedureDeclaration {
@@ -126,17 +159,18 @@ impl ProcedureDeclaration {
             name,
             arguments,
             body,
+            span: Span::default(),
         }
     }
 }
 
-#[derive(Clone, Serialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub enum BinaryOperation {
     Logic(LogicalOperation),
     Calculation(CalculationOperation),
 }
 
-#[derive(Clone, Serialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct BinaryExpression {
     operation: BinaryOperation,
     left: Value,
@@ -203,42 +237,86 @@ impl BinaryExpression {
     }
 }
 
-#[derive(Clone, Serialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct WhileStatement {
     test: TestType,
     body: Vec<Statement>,
+    #[serde(default)]
+    span: Span,
 }
 
 impl WhileStatement {
     pub fn new(test: TestType, body: Vec<Statement>) -> WhileStatement {
-        WhileStatement { test, body }
+        WhileStatement {
+            test,
+            body,
+            span: Span::default(),
+        }
     }
 }
 pub type TestType = Value;
-#[derive(Clone, Serialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct IfStatement {
     test: TestType,
     consequent: Vec<Statement>,
+    #[serde(default)]
+    span: Span,
 }
 impl IfStatement {
     pub fn new(test: TestType, consequent: Vec<Statement>) -> IfStatement {
-        IfStatement { test, consequent }
+        IfStatement {
+            test,
+            consequent,
+            span: Span::default(),
+        }
+    }
+    pub fn holds(&self, heap: &mut Heap) -> bool {
+        let test = self
+            .test
+            .expect_literal_r(heap)
+            .expect("Literal is expected");
+        test.expect_bool().expect("Bool value is expected")
     }
 }
-#[derive(Clone, Serialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct CallExpression {
     callee: Callee,
     arguments: Vec<Value>,
+    #[serde(default)]
+    span: Span,
 }
 impl CallExpression {
     pub fn new(callee: Callee, arguments: Vec<Value>) -> CallExpression {
-        CallExpression { callee, arguments }
+        CallExpression {
+            callee,
+            arguments,
+            span: Span::default(),
+        }
     }
     pub fn call(&self, heap: &mut Heap) -> Option<Literal> {
         heap.execute_function(&self.callee, &self.arguments)
     }
 }
-#[derive(Clone, Eq, Hash, PartialEq, Serialize, Debug)]
+/// The source lines a statement was parsed from, 1-based and inclusive. ASTs
+/// written without spans get line 0.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    start: usize,
+    end: usize,
+}
+#[allow(dead_code)]
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+    pub fn start(&self) -> usize {
+        self.start
+    }
+    pub fn end(&self) -> usize {
+        self.end
+    }
+}
+#[derive(Clone, Eq, Hash, PartialEq, Serialize, Deserialize, Debug)]
 pub enum Callee {
     Command(CommandType),
     Procedure(String),
@@ -255,6 +333,15 @@ impl AssignmentExpression {
     pub fn right(&self) -> &Value {
         &self.right
     }
+    pub fn slot(&self) -> Option<Slot> {
+        self.slot
+    }
+    pub fn right_mut(&mut self) -> &mut Value {
+        &mut self.right
+    }
+    pub fn set_slot(&mut self, slot: Slot) {
+        self.slot = Some(slot);
+    }
 }
 impl ProcedureDeclaration {
     pub fn name(&self) -> &String {
@@ -266,6 +353,12 @@ impl ProcedureDeclaration {
     pub fn body(&self) -> &Vec<Statement> {
         &self.body
     }
+    pub fn body_mut(&mut self) -> &mut Vec<Statement> {
+        &mut self.body
+    }
+    pub fn span(&self) -> Span {
+        self.span
+    }
 }
 impl BinaryExpression {
     pub fn left(&self) -> &Value {
@@ -274,6 +367,12 @@ impl BinaryExpression {
     pub fn right(&self) -> &Value {
         &self.right
     }
+    pub fn left_mut(&mut self) -> &mut Value {
+        &mut self.left
+    }
+    pub fn right_mut(&mut self) -> &mut Value {
+        &mut self.right
+    }
     pub fn operation(&self) -> &BinaryOperation {
         &self.operation
     }
@@ -285,6 +384,12 @@ impl WhileStatement {
     pub fn body(&self) -> &Vec<Statement> {
         &self.body
     }
+    pub fn test_mut(&mut self) -> &mut TestType {
+        &mut self.test
+    }
+    pub fn body_mut(&mut self) -> &mut Vec<Statement> {
+        &mut self.body
+    }
 }
 impl IfStatement {
     pub fn test(&self) -> &TestType {
@@ -293,6 +398,12 @@ impl IfStatement {
     pub fn consequent(&self) -> &Vec<Statement> {
         &self.consequent
     }
+    pub fn test_mut(&mut self) -> &mut TestType {
+        &mut self.test
+    }
+    pub fn consequent_mut(&mut self) -> &mut Vec<Statement> {
+        &mut self.consequent
+    }
 }
 impl CallExpression {
     pub fn callee(&self) -> &Callee {
@@ -301,5 +412,8 @@ impl CallExpression {
     pub fn arguments(&self) -> &Vec<Value> {
         &self.arguments
     }
+    pub fn arguments_mut(&mut self) -> &mut Vec<Value> {
+        &mut self.arguments
+    }
 }
 //  */
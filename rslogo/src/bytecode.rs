@@ -0,0 +1,135 @@
+mod compiler;
+mod vm;
+
+use serde::Serialize;
+
+use crate::{commands::Literal, heap::Slot};
+
+pub use self::{compiler::Compiler, vm::Vm};
+
+#[derive(Clone, Debug, Serialize)]
+pub enum Op {
+    Push(Literal),
+    Pop,
+    Load(Slot),
+    Store(Slot),
+    AddAssign(Slot),
+
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    And,
+    Or,
+
+    PenUp,
+    PenDown,
+    Forward,
+    Back,
+    Left,
+    Right,
+    SetPenColor,
+    /// Pops blue, green and red.
+    SetPenRgb,
+    /// Pops blue, green, red and the palette index.
+    SetPalette,
+    Turn,
+    SetHeading,
+    SetX,
+    SetY,
+    SetPenSize,
+    /// Pops the radius, then the angle.
+    Arc,
+    Circle,
+    Dot,
+    BeginFill,
+    EndFill,
+    SetFillColor,
+    /// Pops blue, green and red.
+    SetFillRgb,
+    /// Labels are always words, so the text is part of the op.
+    Label(String),
+    SetLabelHeight,
+
+    XCor,
+    YCor,
+    Heading,
+    Color,
+    PenSize,
+
+    Jump(usize),
+    JumpIfFalse(usize),
+    /// Binds a procedure name to one of the compiled `Function`s.
+    Declare {
+        procedure: usize,
+        function: usize,
+    },
+    Call {
+        procedure: usize,
+        argc: usize,
+    },
+    /// A call in tail position: reuses the current frame instead of pushing one.
+    TailCall {
+        procedure: usize,
+        argc: usize,
+    },
+    Return,
+    /// Raised when a statement the interpreter would reject at runtime is reached.
+    Fail(String),
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Function {
+    name: String,
+    params: Vec<Slot>,
+    code: Vec<Op>,
+}
+
+#[allow(dead_code)]
+impl Function {
+    pub fn new(name: String, params: Vec<Slot>, code: Vec<Op>) -> Function {
+        Function { name, params, code }
+    }
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+    pub fn params(&self) -> &Vec<Slot> {
+        &self.params
+    }
+    pub fn code(&self) -> &Vec<Op> {
+        &self.code
+    }
+}
+
+/// A compiled program: the top-level code plus every procedure body, with
+/// variables resolved to heap slots and procedure names to indices.
+#[derive(Clone, Debug, Serialize)]
+pub struct Bytecode {
+    main: Vec<Op>,
+    functions: Vec<Function>,
+    procedures: Vec<String>,
+}
+
+#[allow(dead_code)]
+impl Bytecode {
+    pub fn new(main: Vec<Op>, functions: Vec<Function>, procedures: Vec<String>) -> Bytecode {
+        Bytecode {
+            main,
+            functions,
+            procedures,
+        }
+    }
+    pub fn main(&self) -> &Vec<Op> {
+        &self.main
+    }
+    pub fn functions(&self) -> &Vec<Function> {
+        &self.functions
+    }
+    pub fn procedures(&self) -> &Vec<String> {
+        &self.procedures
+    }
+}
@@ -0,0 +1,289 @@
+use std::{
+    collections::HashMap,
+    mem::{replace, take},
+};
+
+use crate::{
+    ast::{
+        AssignmentExpression, BinaryExpression, BinaryOperation, CallExpression, Callee,
+        IfStatement, ProcedureDeclaration, Statement, WhileStatement,
+    },
+    commands::Value,
+    heap::{Heap, Slot},
+    tokens::{
+        AssignmentType, CalculationOperation, CommandType, IdentifierType, LogicalOperation,
+        QueryType,
+    },
+};
+
+use super::{Bytecode, Function, Op};
+
+/// Variables share the slots of the heap's environment, so resolved statements
+/// compile straight to their slot and the VM can run against the same heap.
+pub struct Compiler<'a> {
+    heap: &'a mut Heap,
+    code: Vec<Op>,
+    functions: Vec<Function>,
+    procedures: HashMap<String, usize>,
+    procedure_names: Vec<String>,
+}
+
+impl<'a> Compiler<'a> {
+    pub fn new(heap: &'a mut Heap) -> Compiler<'a> {
+        Compiler {
+            heap,
+            code: Vec::new(),
+            functions: Vec::new(),
+            procedures: HashMap::new(),
+            procedure_names: Vec::new(),
+        }
+    }
+
+    pub fn compile(statements: &[Statement], heap: &mut Heap) -> Bytecode {
+        let mut compiler = Compiler::new(heap);
+        compiler.compile_block(statements);
+        compiler.finish()
+    }
+
+    fn finish(self) -> Bytecode {
+        Bytecode::new(self.code, self.functions, self.procedure_names)
+    }
+
+    fn variable_slot(&mut self, name: &str) -> Slot {
+        self.heap.intern(name)
+    }
+
+    fn procedure_id(&mut self, name: &str) -> usize {
+        if let Some(id) = self.procedures.get(name) {
+            return *id;
+        }
+        let id = self.procedure_names.len();
+        self.procedures.insert(name.to_string(), id);
+        self.procedure_names.push(name.to_string());
+        id
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.code.push(op);
+        self.code.len() - 1
+    }
+
+    fn patch(&mut self, at: usize) {
+        let target = self.code.len();
+        match &mut self.code[at] {
+            Op::Jump(to) | Op::JumpIfFalse(to) => *to = target,
+            op => panic!("Can not patch {:?}", op),
+        }
+    }
+
+    fn compile_block(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.compile_statement(statement);
+        }
+    }
+
+    /// Compiles a block whose last statement is in tail position, turning a
+    /// procedure call there (or at the end of a trailing `IF`) into a `TailCall`.
+    fn compile_tail_block(&mut self, statements: &[Statement]) {
+        let Some((last, rest)) = statements.split_last() else {
+            return;
+        };
+        self.compile_block(rest);
+        match last {
+            Statement::CallExpression(call) => match call.callee() {
+                Callee::Procedure(name) => {
+                    for argument in call.arguments() {
+                        self.compile_value(argument);
+                    }
+                    let procedure = self.procedure_id(name);
+                    self.emit(Op::TailCall {
+                        procedure,
+                        argc: call.arguments().len(),
+                    });
+                }
+                _ => self.compile_statement(last),
+            },
+            Statement::If(if_statement) => {
+                self.compile_value(if_statement.test());
+                let skip = self.emit(Op::JumpIfFalse(0));
+                self.compile_tail_block(if_statement.consequent());
+                self.patch(skip);
+            }
+            _ => self.compile_statement(last),
+        }
+    }
+
+    fn compile_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::CallExpression(call) => {
+                if self.compile_call(call) {
+                    self.emit(Op::Pop);
+                }
+            }
+            Statement::AssignmentExpression(assign) => self.compile_assignment(assign),
+            Statement::If(if_statement) => self.compile_if(if_statement),
+            Statement::While(while_statement) => self.compile_while(while_statement),
+            Statement::ProcedureDeclaration(procedure) => self.compile_procedure(procedure),
+        }
+    }
+
+    /// Returns whether the call leaves a value on the stack.
+    fn compile_call(&mut self, call: &CallExpression) -> bool {
+        for argument in call.arguments() {
+            self.compile_value(argument);
+        }
+        match call.callee() {
+            Callee::Command(command) => {
+                let op = match command {
+                    CommandType::Penup => Op::PenUp,
+                    CommandType::Pendown => Op::PenDown,
+                    CommandType::Forward => Op::Forward,
+                    CommandType::Back => Op::Back,
+                    CommandType::Left => Op::Left,
+                    CommandType::Right => Op::Right,
+                    CommandType::Setpencolor if call.arguments().len() == 3 => Op::SetPenRgb,
+                    CommandType::Setpencolor => Op::SetPenColor,
+                    CommandType::Turn => Op::Turn,
+                    CommandType::Setheading => Op::SetHeading,
+                    CommandType::Setx => Op::SetX,
+                    CommandType::Sety => Op::SetY,
+                    CommandType::Setpensize => Op::SetPenSize,
+                    CommandType::Setpalette => Op::SetPalette,
+                    CommandType::Arc => Op::Arc,
+                    CommandType::Circle => Op::Circle,
+                    CommandType::Dot => Op::Dot,
+                    CommandType::Beginfill => Op::BeginFill,
+                    CommandType::Endfill => Op::EndFill,
+                    CommandType::Setfillcolor if call.arguments().len() == 3 => Op::SetFillRgb,
+                    CommandType::Setfillcolor => Op::SetFillColor,
+                    CommandType::Label => match call.arguments().first() {
+                        Some(Value::Word(word)) => Op::Label(word.clone()),
+                        _ => Op::Fail("Expect a word".to_string()),
+                    },
+                    CommandType::Setlabelheight => Op::SetLabelHeight,
+                };
+                self.emit(op);
+                false
+            }
+            Callee::Query(query) => {
+                let op = match query {
+                    QueryType::Xcor => Op::XCor,
+                    QueryType::Ycor => Op::YCor,
+                    QueryType::Heading => Op::Heading,
+                    QueryType::Color => Op::Color,
+                    QueryType::Pensize => Op::PenSize,
+                };
+                self.emit(op);
+                true
+            }
+            Callee::Procedure(name) => {
+                let procedure = self.procedure_id(name);
+                self.emit(Op::Call {
+                    procedure,
+                    argc: call.arguments().len(),
+                });
+                false
+            }
+        }
+    }
+
+    fn compile_assignment(&mut self, assign: &AssignmentExpression) {
+        self.compile_value(assign.right());
+        let slot = match assign.slot() {
+            Some(slot) => slot,
+            None => self.variable_slot(assign.left()),
+        };
+        match assign.operation() {
+            AssignmentType::Make => self.emit(Op::Store(slot)),
+            AssignmentType::Addassign => self.emit(Op::AddAssign(slot)),
+        };
+    }
+
+    fn compile_if(&mut self, if_statement: &IfStatement) {
+        self.compile_value(if_statement.test());
+        let skip = self.emit(Op::JumpIfFalse(0));
+        self.compile_block(if_statement.consequent());
+        self.patch(skip);
+    }
+
+    fn compile_while(&mut self, while_statement: &WhileStatement) {
+        let start = self.code.len();
+        self.compile_value(while_statement.test());
+        let exit = self.emit(Op::JumpIfFalse(0));
+        self.compile_block(while_statement.body());
+        self.emit(Op::Jump(start));
+        self.patch(exit);
+    }
+
+    fn compile_procedure(&mut self, procedure: &ProcedureDeclaration) {
+        let params = procedure
+            .arguments()
+            .iter()
+            .map(|argument| self.variable_slot(argument))
+            .collect();
+        let outer = take(&mut self.code);
+        self.compile_tail_block(procedure.body());
+        self.emit(Op::Return);
+        let code = replace(&mut self.code, outer);
+
+        let function = self.functions.len();
+        self.functions
+            .push(Function::new(procedure.name().clone(), params, code));
+        let procedure = self.procedure_id(procedure.name());
+        self.emit(Op::Declare {
+            procedure,
+            function,
+        });
+    }
+
+    fn compile_value(&mut self, value: &Value) {
+        match value {
+            Value::Variable(variable) => {
+                self.emit(Op::Load(variable.slot()));
+            }
+            Value::Identifier(IdentifierType::Variable(name)) => {
+                let slot = self.variable_slot(name);
+                self.emit(Op::Load(slot));
+            }
+            Value::Identifier(IdentifierType::Procedure(_)) => {
+                self.emit(Op::Fail("Expect a variable or literal".to_string()));
+            }
+            Value::Literal(literal) => {
+                self.emit(Op::Push(literal.clone()));
+            }
+            Value::Statement(call) => {
+                if !self.compile_call(call) {
+                    self.emit(Op::Fail(format!(
+                        "{:?} does not return a value",
+                        call.callee()
+                    )));
+                }
+            }
+            Value::BinaryExpression(binary) => self.compile_binary(binary),
+            // Only LABEL takes a word, and `Op::Label` holds it.
+            Value::Word(_) => {}
+        }
+    }
+
+    fn compile_binary(&mut self, binary: &BinaryExpression) {
+        self.compile_value(binary.left());
+        self.compile_value(binary.right());
+        let op = match binary.operation() {
+            BinaryOperation::Calculation(calculation) => match calculation {
+                CalculationOperation::Plus => Op::Add,
+                CalculationOperation::Dash => Op::Sub,
+                CalculationOperation::Star => Op::Mul,
+                CalculationOperation::Slash => Op::Div,
+            },
+            BinaryOperation::Logic(logic) => match logic {
+                LogicalOperation::And => Op::And,
+                LogicalOperation::Or => Op::Or,
+                LogicalOperation::Eq => Op::Eq,
+                LogicalOperation::Ne => Op::Ne,
+                LogicalOperation::Lt => Op::Lt,
+                LogicalOperation::Gt => Op::Gt,
+            },
+        };
+        self.emit(op);
+    }
+}
@@ -0,0 +1,258 @@
+use std::{
+    mem::replace,
+    ops::{Add, Div, Mul, Sub},
+};
+
+use crate::{
+    ast::Callee,
+    commands::Literal,
+    display_list::Rgb,
+    heap::{Heap, Shadowed},
+};
+
+use super::{Bytecode, Op};
+
+struct Frame {
+    function: Option<usize>,
+    ip: usize,
+    shadowed: Shadowed,
+}
+
+pub struct Vm<'a> {
+    bytecode: &'a Bytecode,
+    stack: Vec<Literal>,
+    procedures: Vec<Option<usize>>,
+    frames: Vec<Frame>,
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(bytecode: &'a Bytecode) -> Vm<'a> {
+        Vm {
+            bytecode,
+            stack: Vec::new(),
+            procedures: vec![None; bytecode.procedures().len()],
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn run(&mut self, heap: &mut Heap) {
+        let bytecode = self.bytecode;
+        let mut function: Option<usize> = None;
+        let mut ip = 0;
+        loop {
+            let code = match function {
+                Some(index) => bytecode.functions()[index].code(),
+                None => bytecode.main(),
+            };
+            let Some(op) = code.get(ip) else {
+                break;
+            };
+            ip += 1;
+            match op {
+                Op::Push(literal) => self.stack.push(literal.clone()),
+                Op::Pop => {
+                    self.pop();
+                }
+                Op::Load(slot) => {
+                    let value = heap
+                        .variable(*slot)
+                        .cloned()
+                        .expect("variable does not exist");
+                    self.stack.push(value);
+                }
+                Op::Store(slot) => {
+                    let value = self.pop();
+                    heap.set_variable(*slot, value);
+                }
+                Op::AddAssign(slot) => {
+                    let value = self.pop();
+                    let current = heap.take_variable(*slot).expect("Variable does not exist");
+                    heap.set_variable(*slot, current.add(value));
+                }
+
+                Op::Add => self.binary(|left, right| left.add(right)),
+                Op::Sub => self.binary(|left, right| left.sub(right)),
+                Op::Mul => self.binary(|left, right| left.mul(right)),
+                Op::Div => self.binary(|left, right| left.div(right)),
+                Op::Eq => self.binary(|left, right| Literal::Bool(left.eq(&right))),
+                Op::Ne => self.binary(|left, right| Literal::Bool(left.ne(&right))),
+                Op::Lt => self.binary(|left, right| Literal::Bool(left.lt(&right))),
+                Op::Gt => self.binary(|left, right| Literal::Bool(left.gt(&right))),
+                Op::And => self.binary(|left, right| {
+                    Literal::Bool(
+                        left.expect_bool().expect("Bool value is expected")
+                            && right.expect_bool().expect("Bool value is expected"),
+                    )
+                }),
+                Op::Or => self.binary(|left, right| {
+                    Literal::Bool(
+                        left.expect_bool().expect("Bool value is expected")
+                            || right.expect_bool().expect("Bool value is expected"),
+                    )
+                }),
+
+                Op::PenUp => heap.mut_turtle().pen_up(),
+                Op::PenDown => heap.mut_turtle().pen_down(),
+                Op::Forward => {
+                    if let Some(distance) = self.pop().expect_float() {
+                        heap.mut_turtle().move_forward(distance);
+                    }
+                }
+                Op::Back => {
+                    if let Some(distance) = self.pop().expect_float() {
+                        heap.mut_turtle().move_back(distance);
+                    }
+                }
+                Op::Left => {
+                    if let Some(distance) = self.pop().expect_float() {
+                        heap.mut_turtle().move_left(distance);
+                    }
+                }
+                Op::Right => {
+                    if let Some(distance) = self.pop().expect_float() {
+                        heap.mut_turtle().move_right(distance);
+                    }
+                }
+                Op::SetPenColor => {
+                    let color = self.pop().expect_int().expect("Expect an integer");
+                    heap.mut_turtle().set_color(color);
+                }
+                Op::SetPenRgb => {
+                    let rgb = self.pop_rgb();
+                    heap.mut_turtle().set_rgb(rgb);
+                }
+                Op::SetPalette => {
+                    let rgb = self.pop_rgb();
+                    let color = self.pop().expect_int().expect("Expect an integer");
+                    heap.mut_turtle().set_palette(color, rgb);
+                }
+                Op::Turn => {
+                    let degree = self.pop().expect_int().expect("Expect an integer");
+                    heap.mut_turtle().turn(degree);
+                }
+                Op::SetHeading => {
+                    let degree = self.pop().expect_int().expect("Expect an integer");
+                    heap.mut_turtle().set_direction(degree);
+                }
+                Op::SetX => {
+                    if let Some(x) = self.pop().expect_float() {
+                        heap.mut_turtle().set_x(x);
+                    }
+                }
+                Op::SetY => {
+                    if let Some(y) = self.pop().expect_float() {
+                        heap.mut_turtle().set_y(y);
+                    }
+                }
+                Op::Arc => {
+                    let radius = self.pop().expect_float();
+                    let angle = self.pop().expect_float();
+                    if let (Some(angle), Some(radius)) = (angle, radius) {
+                        heap.mut_turtle().arc(angle, radius);
+                    }
+                }
+                Op::Circle => {
+                    if let Some(radius) = self.pop().expect_float() {
+                        heap.mut_turtle().circle(radius);
+                    }
+                }
+                Op::Dot => heap.mut_turtle().dot(),
+                Op::BeginFill => heap.mut_turtle().begin_fill(),
+                Op::EndFill => heap.mut_turtle().end_fill(),
+                Op::SetFillColor => {
+                    let color = self.pop().expect_int().expect("Expect an integer");
+                    heap.mut_turtle().set_fill_color(color);
+                }
+                Op::SetFillRgb => {
+                    let rgb = self.pop_rgb();
+                    heap.mut_turtle().set_fill_rgb(rgb);
+                }
+                Op::Label(text) => heap.mut_turtle().label(text),
+                Op::SetLabelHeight => {
+                    let height = self.pop().expect_float().expect("Expect a number");
+                    heap.mut_turtle().set_label_height(height);
+                }
+                Op::SetPenSize => {
+                    let size = self.pop().expect_float().expect("Expect a number");
+                    heap.mut_turtle().set_pen_size(size);
+                }
+
+                Op::XCor => self.stack.push(Literal::Float(heap.turtle().x())),
+                Op::YCor => self.stack.push(Literal::Float(heap.turtle().y())),
+                Op::Heading => self.stack.push(Literal::Int(heap.turtle().direction())),
+                Op::Color => self.stack.push(Literal::Int(heap.turtle().color())),
+                Op::PenSize => self.stack.push(Literal::Float(heap.turtle().pen_size())),
+
+                Op::Jump(target) => ip = *target,
+                Op::JumpIfFalse(target) => {
+                    let test = self.pop();
+                    if !test.expect_bool().expect("Bool value is expected") {
+                        ip = *target;
+                    }
+                }
+                Op::Declare {
+                    procedure,
+                    function,
+                } => self.procedures[*procedure] = Some(*function),
+                Op::Call { procedure, argc } => {
+                    let callee = self.callee(*procedure);
+                    let args = self.stack.split_off(self.stack.len() - argc);
+                    let mut shadowed = Vec::new();
+                    heap.bind_arguments(bytecode.functions()[callee].params(), args, &mut shadowed);
+                    self.frames.push(Frame {
+                        function: function.replace(callee),
+                        ip: replace(&mut ip, 0),
+                        shadowed,
+                    });
+                }
+                Op::TailCall { procedure, argc } => {
+                    let callee = self.callee(*procedure);
+                    let args = self.stack.split_off(self.stack.len() - argc);
+                    let frame = self
+                        .frames
+                        .last_mut()
+                        .expect("Tail call outside of a procedure");
+                    heap.bind_arguments(
+                        bytecode.functions()[callee].params(),
+                        args,
+                        &mut frame.shadowed,
+                    );
+                    function = Some(callee);
+                    ip = 0;
+                }
+                Op::Return => {
+                    let frame = self.frames.pop().expect("Return outside of a procedure");
+                    heap.restore_arguments(frame.shadowed);
+                    function = frame.function;
+                    ip = frame.ip;
+                }
+                Op::Fail(message) => panic!("{}", message),
+            }
+        }
+    }
+
+    fn callee(&self, procedure: usize) -> usize {
+        self.procedures[procedure].unwrap_or_else(|| {
+            panic!(
+                "Function {:?} is not found",
+                Callee::Procedure(self.bytecode.procedures()[procedure].clone())
+            )
+        })
+    }
+
+    fn pop(&mut self) -> Literal {
+        self.stack.pop().expect("Value stack is empty")
+    }
+
+    fn pop_rgb(&mut self) -> Rgb {
+        let mut channel = || self.pop().expect_int().expect("Expect an integer");
+        let (blue, green, red) = (channel(), channel(), channel());
+        Rgb::from_channels(red, green, blue)
+    }
+
+    fn binary(&mut self, operation: impl FnOnce(Literal, Literal) -> Literal) {
+        let right = self.pop();
+        let left = self.pop();
+        self.stack.push(operation(left, right));
+    }
+}
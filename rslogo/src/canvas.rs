@@ -0,0 +1,245 @@
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+use unsvg::{Color, Image};
+
+use crate::display_list::{Arc, Dot, Fill, Label};
+
+/// unsvg only draws straight lines, so arcs are drawn as chords this many
+/// degrees wide.
+pub const ARC_STEP: f32 = 2.0;
+
+/// A straight stroke the turtle drew. `direction` and `length` describe the
+/// move that led from `from` to `to`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Line {
+    from: (f32, f32),
+    to: (f32, f32),
+    direction: i32,
+    length: f32,
+    color: Color,
+}
+
+#[allow(dead_code)]
+impl Line {
+    /// Both ends are rounded the way `unsvg` rounds them.
+    pub fn new(from: (f32, f32), direction: i32, length: f32, color: Color) -> Line {
+        Line {
+            from: (quantize(from.0), quantize(from.1)),
+            to: end_point(from, direction, length),
+            direction,
+            length,
+            color,
+        }
+    }
+    pub fn from(&self) -> (f32, f32) {
+        self.from
+    }
+    pub fn to(&self) -> (f32, f32) {
+        self.to
+    }
+    pub fn direction(&self) -> i32 {
+        self.direction
+    }
+    pub fn length(&self) -> f32 {
+        self.length
+    }
+    pub fn color(&self) -> Color {
+        self.color
+    }
+}
+
+/// Where the turtle draws. The turtle works out its own geometry, so a canvas
+/// only has to put strokes somewhere.
+pub trait Canvas {
+    fn dimensions(&self) -> (u32, u32);
+    fn line(&mut self, line: &Line) -> Result<(), String>;
+    fn arc(&mut self, _arc: &Arc) -> Result<(), String> {
+        Ok(())
+    }
+    fn dot(&mut self, _dot: &Dot) -> Result<(), String> {
+        Ok(())
+    }
+    fn fill(&mut self, _fill: &Fill) -> Result<(), String> {
+        Ok(())
+    }
+    fn label(&mut self, _label: &Label) -> Result<(), String> {
+        Ok(())
+    }
+    /// Writes the drawing to a file in the format its extension names.
+    fn save(&self, _path: &Path) -> Result<(), String> {
+        Err("This canvas can not be saved".to_string())
+    }
+    /// The unsvg image behind the canvas, if there is one.
+    fn image(&self) -> Option<&Image> {
+        None
+    }
+}
+
+/// Draws into an `unsvg::Image`, which can be saved as SVG or PNG.
+pub struct UnsvgCanvas {
+    image: Image,
+}
+
+impl UnsvgCanvas {
+    pub fn new(image: Image) -> UnsvgCanvas {
+        UnsvgCanvas { image }
+    }
+}
+
+impl Canvas for UnsvgCanvas {
+    fn dimensions(&self) -> (u32, u32) {
+        self.image.get_dimensions()
+    }
+    fn line(&mut self, line: &Line) -> Result<(), String> {
+        let (x, y) = line.from;
+        self.image
+            .draw_simple_line(x, y, line.direction, line.length, line.color)
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+    fn arc(&mut self, arc: &Arc) -> Result<(), String> {
+        for line in chords(arc) {
+            self.line(&line)?;
+        }
+        Ok(())
+    }
+    /// A pixel-long line, since unsvg strokes are a pixel wide.
+    fn dot(&mut self, dot: &Dot) -> Result<(), String> {
+        let (x, y) = dot.center();
+        self.line(&Line::new((x - 0.5, y), 90, 1.0, dot.color().into()))
+    }
+    /// unsvg has no text, so labels are drawn in the stroke font.
+    fn label(&mut self, label: &Label) -> Result<(), String> {
+        for line in letters(label) {
+            self.line(&line)?;
+        }
+        Ok(())
+    }
+    fn save(&self, path: &Path) -> Result<(), String> {
+        match path.extension().and_then(|s| s.to_str()) {
+            Some("svg") => self
+                .image
+                .save_svg(path)
+                .map_err(|e| format!("Error saving svg: {e}")),
+            Some("png") => self
+                .image
+                .save_png(path)
+                .map_err(|e| format!("Error saving png: {e}")),
+            _ => Err("File extension not supported".to_string()),
+        }
+    }
+    fn image(&self) -> Option<&Image> {
+        Some(&self.image)
+    }
+}
+
+/// Keeps every line in drawing order, for tests that check what was drawn.
+/// The lines are shared, so they stay readable once the canvas is handed to
+/// a turtle.
+pub struct RecordingCanvas {
+    width: u32,
+    height: u32,
+    lines: Rc<RefCell<Vec<Line>>>,
+}
+
+#[allow(dead_code)]
+impl RecordingCanvas {
+    pub fn new(width: u32, height: u32) -> RecordingCanvas {
+        RecordingCanvas {
+            width,
+            height,
+            lines: Rc::default(),
+        }
+    }
+    pub fn lines(&self) -> Rc<RefCell<Vec<Line>>> {
+        Rc::clone(&self.lines)
+    }
+}
+
+impl Canvas for RecordingCanvas {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+    fn line(&mut self, line: &Line) -> Result<(), String> {
+        self.lines.borrow_mut().push(*line);
+        Ok(())
+    }
+}
+
+/// Draws nothing, for headless runs and benchmarks.
+pub struct NullCanvas {
+    width: u32,
+    height: u32,
+}
+
+#[allow(dead_code)]
+impl NullCanvas {
+    pub fn new(width: u32, height: u32) -> NullCanvas {
+        NullCanvas { width, height }
+    }
+}
+
+impl Canvas for NullCanvas {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+    fn line(&mut self, _line: &Line) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Where a move of `length` in `direction` ends, rounded to 1/256 of a pixel
+/// exactly as `unsvg` rounds it. Heading 0 is up and angles grow clockwise.
+pub fn end_point(from: (f32, f32), direction: i32, length: f32) -> (f32, f32) {
+    let (x, y) = (quantize(from.0), quantize(from.1));
+    let radians = ((direction as f32) - 90.0).to_radians();
+    (
+        quantize(x + radians.cos() * length),
+        quantize(y + radians.sin() * length),
+    )
+}
+
+/// The straight lines `UnsvgCanvas` draws for an arc. Each chord runs
+/// between two points on the circle, so the error does not build up.
+pub fn chords(arc: &Arc) -> Vec<Line> {
+    let sweep = arc.sweep().clamp(-360.0, 360.0);
+    let sign = sweep.signum();
+    let steps = (sweep.abs() / ARC_STEP).ceil() as i32;
+    (0..steps)
+        .map(|step| {
+            let from = step as f32 * ARC_STEP;
+            let to = (from + ARC_STEP).min(sweep.abs());
+            let middle = arc.start() + sign * (from + to) / 2.0;
+            let length = 2.0 * arc.radius() * ((to - from) / 2.0).to_radians().sin();
+            Line::new(
+                arc.point(sign * from),
+                (middle + sign * 90.0).round() as i32,
+                length,
+                arc.color().into(),
+            )
+        })
+        .collect()
+}
+
+/// The straight lines `UnsvgCanvas` draws for a label's strokes.
+pub fn letters(label: &Label) -> Vec<Line> {
+    label
+        .strokes()
+        .iter()
+        .flat_map(|stroke| stroke.windows(2))
+        .map(|points| {
+            let (from, to) = (points[0], points[1]);
+            let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+            Line::new(
+                from,
+                dx.atan2(-dy).to_degrees().round() as i32,
+                dx.hypot(dy),
+                label.color().into(),
+            )
+        })
+        .collect()
+}
+
+fn quantize(value: f32) -> f32 {
+    (value * 256.0).round() / 256.0
+}
@@ -5,18 +5,51 @@ mod queries;
 mod variables;
 use crate::{
     ast::{BinaryExpression, CallExpression, Statement},
-    heap::Heap,
+    heap::{Heap, Slot},
     tokens::{IdentifierType, ValueType},
 };
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize, Serializer};
 
-#[derive(Clone, Serialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub enum Value {
     Identifier(IdentifierType),
     Literal(Literal),
     Statement(Box<CallExpression>),
     BinaryExpression(Box<BinaryExpression>),
+    /// The text LABEL draws. It has no value in expressions.
+    Word(String),
+    /// A variable read whose slot was assigned by the resolver. It serializes
+    /// exactly like the `Identifier` it replaced, so it reads back as one.
+    #[serde(
+        rename = "Identifier",
+        serialize_with = "serialize_variable",
+        skip_deserializing
+    )]
+    Variable(Variable),
+}
+
+#[derive(Clone, Debug)]
+pub struct Variable {
+    name: String,
+    slot: Slot,
+}
+impl Variable {
+    pub fn new(name: String, slot: Slot) -> Variable {
+        Variable { name, slot }
+    }
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+    pub fn slot(&self) -> Slot {
+        self.slot
+    }
+}
+fn serialize_variable<S: Serializer>(
+    variable: &Variable,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    IdentifierType::Variable(variable.name.clone()).serialize(serializer)
 }
 
 pub type Literal = ValueType;
@@ -51,8 +84,19 @@ impl Literal {
 }
 
 impl Value {
+    pub fn expect_word(&self) -> Option<&String> {
+        match self {
+            Value::Word(word) => Some(word),
+            _ => None,
+        }
+    }
     pub fn expect_literal_r(&self, heap: &mut Heap) -> Option<Literal> {
         match self {
+            Value::Variable(variable) => Some(
+                heap.variable(variable.slot)
+                    .cloned()
+                    .expect("variable does not exist"),
+            ),
             Value::Identifier(identifier) => Some(
                 heap.variable_value(
                     &identifier
@@ -64,21 +108,26 @@ impl Value {
             Value::Literal(literal) => Some(literal.clone()),
             Value::Statement(statement) => Some(statement.call(heap).unwrap()),
             Value::BinaryExpression(binary) => Some(binary.calculate(heap)),
+            Value::Word(_) => None,
         }
     }
 }
 
 pub trait Callable {
     fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal>;
+    fn as_procedure(&self) -> Option<&Procedure> {
+        None
+    }
 }
 
 pub trait Assignable {
-    fn declare(&self, left: &str, right: &Value, heap: &mut Heap);
+    fn declare(&self, left: Slot, right: &Value, heap: &mut Heap);
 }
 
 pub struct CallableFactory {}
 pub struct AssignableFactory {}
 pub struct Procedure {
-    arguments: Vec<String>,
+    name: String,
+    arguments: Vec<Slot>,
     body: Vec<Statement>,
 }
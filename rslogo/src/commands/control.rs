@@ -1,6 +1,6 @@
 #![allow(unused_variables)]
 
-use crate::heap::Heap;
+use crate::{display_list::Rgb, heap::Heap};
 
 use super::{Callable, Literal, Value};
 
@@ -24,7 +24,7 @@ pub struct ForwardCommand {}
 
 impl Callable for ForwardCommand {
     fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
-        let num_pixel = args.get(0)?.expect_literal_r(heap)?.expect_float()?;
+        let num_pixel = args.first()?.expect_literal_r(heap)?.expect_float()?;
         heap.mut_turtle().move_forward(num_pixel);
         None
     }
@@ -34,7 +34,7 @@ pub struct BackCommand {}
 
 impl Callable for BackCommand {
     fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
-        let num_pixel = args.get(0)?.expect_literal_r(heap)?.expect_float()?;
+        let num_pixel = args.first()?.expect_literal_r(heap)?.expect_float()?;
         heap.mut_turtle().move_back(num_pixel);
         None
     }
@@ -44,7 +44,7 @@ pub struct LeftCommand {}
 
 impl Callable for LeftCommand {
     fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
-        let num_pixel = args.get(0)?.expect_literal_r(heap)?.expect_float()?;
+        let num_pixel = args.first()?.expect_literal_r(heap)?.expect_float()?;
         heap.mut_turtle().move_left(num_pixel);
         None
     }
@@ -53,7 +53,7 @@ pub struct RightCommand {}
 
 impl Callable for RightCommand {
     fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
-        let num_pixel = args.get(0)?.expect_literal_r(heap)?.expect_float()?;
+        let num_pixel = args.first()?.expect_literal_r(heap)?.expect_float()?;
         heap.mut_turtle().move_right(num_pixel);
         None
     }
@@ -62,22 +62,113 @@ pub struct SetPenColorCommand {}
 
 impl Callable for SetPenColorCommand {
     fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
-        let color = args
-            .get(0)?
-            .expect_literal_r(heap)?
-            .expect_int()
-            .expect("Expect an integer");
-        heap.mut_turtle().set_color(color);
+        let channels = integers(args, heap)?;
+        match channels[..] {
+            [red, green, blue] => heap
+                .mut_turtle()
+                .set_rgb(Rgb::from_channels(red, green, blue)),
+            [color, ..] => heap.mut_turtle().set_color(color),
+            [] => return None,
+        }
+        None
+    }
+}
+
+pub struct ArcCommand {}
+
+impl Callable for ArcCommand {
+    fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
+        let angle = args.first()?.expect_literal_r(heap)?.expect_float()?;
+        let radius = args.get(1)?.expect_literal_r(heap)?.expect_float()?;
+        heap.mut_turtle().arc(angle, radius);
+        None
+    }
+}
+
+pub struct CircleCommand {}
+
+impl Callable for CircleCommand {
+    fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
+        let radius = args.first()?.expect_literal_r(heap)?.expect_float()?;
+        heap.mut_turtle().circle(radius);
+        None
+    }
+}
+
+pub struct DotCommand {}
+
+impl Callable for DotCommand {
+    fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
+        heap.mut_turtle().dot();
+        None
+    }
+}
+
+pub struct SetFillColorCommand {}
+
+impl Callable for SetFillColorCommand {
+    fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
+        let channels = integers(args, heap)?;
+        match channels[..] {
+            [red, green, blue] => heap
+                .mut_turtle()
+                .set_fill_rgb(Rgb::from_channels(red, green, blue)),
+            [color, ..] => heap.mut_turtle().set_fill_color(color),
+            [] => return None,
+        }
+        None
+    }
+}
+
+pub struct BeginFillCommand {}
+
+impl Callable for BeginFillCommand {
+    fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
+        heap.mut_turtle().begin_fill();
         None
     }
 }
 
+pub struct EndFillCommand {}
+
+impl Callable for EndFillCommand {
+    fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
+        heap.mut_turtle().end_fill();
+        None
+    }
+}
+
+pub struct SetPaletteCommand {}
+
+impl Callable for SetPaletteCommand {
+    fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
+        let channels = integers(args, heap)?;
+        if let [color, red, green, blue] = channels[..] {
+            heap.mut_turtle()
+                .set_palette(color, Rgb::from_channels(red, green, blue));
+        }
+        None
+    }
+}
+
+fn integers(args: &[Value], heap: &mut Heap) -> Option<Vec<i32>> {
+    args.iter()
+        .map(|arg| {
+            Some(
+                arg.expect_literal_r(heap)?
+                    .expect_int()
+                    .expect("Expect an integer"),
+            )
+        })
+        .collect()
+}
+
 pub struct TurnCommand {}
 
 impl Callable for TurnCommand {
     fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
         let degree = args
-            .get(0)?
+            .first()?
             .expect_literal_r(heap)?
             .expect_int()
             .expect("Expect an integer");
@@ -90,7 +181,7 @@ pub struct SetHeadingCommand {}
 impl Callable for SetHeadingCommand {
     fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
         let color = args
-            .get(0)?
+            .first()?
             .expect_literal_r(heap)?
             .expect_int()
             .expect("Expect an integer");
@@ -101,7 +192,7 @@ impl Callable for SetHeadingCommand {
 pub struct SetXCommand {}
 impl Callable for SetXCommand {
     fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
-        let color = args.get(0)?.expect_literal_r(heap)?.expect_float()?;
+        let color = args.first()?.expect_literal_r(heap)?.expect_float()?;
         heap.mut_turtle().set_x(color);
         None
     }
@@ -110,8 +201,45 @@ pub struct SetYCommand {}
 
 impl Callable for SetYCommand {
     fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
-        let color = args.get(0)?.expect_literal_r(heap)?.expect_float()?;
+        let color = args.first()?.expect_literal_r(heap)?.expect_float()?;
         heap.mut_turtle().set_y(color);
         None
     }
 }
+pub struct SetPenSizeCommand {}
+
+impl Callable for SetPenSizeCommand {
+    fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
+        let size = args
+            .first()?
+            .expect_literal_r(heap)?
+            .expect_float()
+            .expect("Expect a number");
+        heap.mut_turtle().set_pen_size(size);
+        None
+    }
+}
+
+pub struct LabelCommand {}
+
+impl Callable for LabelCommand {
+    fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
+        let text = args.first()?.expect_word().expect("Expect a word");
+        heap.mut_turtle().label(text);
+        None
+    }
+}
+
+pub struct SetLabelHeightCommand {}
+
+impl Callable for SetLabelHeightCommand {
+    fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
+        let height = args
+            .first()?
+            .expect_literal_r(heap)?
+            .expect_float()
+            .expect("Expect a number");
+        heap.mut_turtle().set_label_height(height);
+        None
+    }
+}
@@ -4,10 +4,12 @@ use crate::{
     ast::Callee,
     commands::{
         control::{
-            BackCommand, ForwardCommand, LeftCommand, PenDownCommand, PenUpCommand, RightCommand,
-            SetHeadingCommand, SetPenColorCommand, SetXCommand, SetYCommand, TurnCommand,
+            ArcCommand, BackCommand, BeginFillCommand, CircleCommand, DotCommand, EndFillCommand,
+            ForwardCommand, LabelCommand, LeftCommand, PenDownCommand, PenUpCommand, RightCommand,
+            SetFillColorCommand, SetHeadingCommand, SetLabelHeightCommand, SetPaletteCommand,
+            SetPenColorCommand, SetPenSizeCommand, SetXCommand, SetYCommand, TurnCommand,
         },
-        queries::{ColorCommand, HeadingCommand, XCorCommand, YCorCommand},
+        queries::{ColorCommand, HeadingCommand, PenSizeCommand, XCorCommand, YCorCommand},
     },
     tokens::{AssignmentType, CommandType, QueryType},
 };
@@ -32,12 +34,23 @@ impl CallableFactory {
                 CommandType::Turn => Some(Rc::new(TurnCommand {})),
                 CommandType::Setx => Some(Rc::new(SetXCommand {})),
                 CommandType::Sety => Some(Rc::new(SetYCommand {})),
+                CommandType::Setpensize => Some(Rc::new(SetPenSizeCommand {})),
+                CommandType::Setpalette => Some(Rc::new(SetPaletteCommand {})),
+                CommandType::Arc => Some(Rc::new(ArcCommand {})),
+                CommandType::Circle => Some(Rc::new(CircleCommand {})),
+                CommandType::Dot => Some(Rc::new(DotCommand {})),
+                CommandType::Beginfill => Some(Rc::new(BeginFillCommand {})),
+                CommandType::Endfill => Some(Rc::new(EndFillCommand {})),
+                CommandType::Setfillcolor => Some(Rc::new(SetFillColorCommand {})),
+                CommandType::Label => Some(Rc::new(LabelCommand {})),
+                CommandType::Setlabelheight => Some(Rc::new(SetLabelHeightCommand {})),
             },
             Callee::Query(query) => match query {
                 QueryType::Color => Some(Rc::new(ColorCommand {})),
                 QueryType::Heading => Some(Rc::new(HeadingCommand {})),
                 QueryType::Xcor => Some(Rc::new(XCorCommand {})),
                 QueryType::Ycor => Some(Rc::new(YCorCommand {})),
+                QueryType::Pensize => Some(Rc::new(PenSizeCommand {})),
             },
             _ => None,
         }
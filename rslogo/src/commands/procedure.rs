@@ -1,55 +1,100 @@
-use std::collections::HashMap;
+use std::rc::Rc;
 
 use crate::{
-    ast::{Executable, Statement},
-    heap::Heap,
+    ast::{Callee, Executable, Statement},
+    heap::{Heap, Shadowed, Slot},
 };
 
-use super::{Callable, Literal, Procedure};
+use super::{Callable, Literal, Procedure, Value};
+
+/// A procedure call found in tail position, with its arguments already
+/// evaluated in the caller's frame.
+struct TailCall {
+    callee: Callee,
+    args: Vec<Literal>,
+}
 
 impl Callable for Procedure {
-    fn call(&self, args: &[super::Value], heap: &mut Heap) -> Option<super::Literal> {
-        // println!("CALL PRECEDURE");
-        let args_value: Vec<Literal> = args
-            .iter()
-            .map(|value| {
-                value
-                    .expect_literal_r(heap)
-                    .expect("It cannot be convert to Literal Value")
-            })
-            .collect();
-        let buf = self.handel_variable_conflicts(heap);
-        self.push_arguments(heap, &args_value);
-        for statement in self.body.iter() {
-            statement.execute(heap);
+    fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
+        let args_value = evaluate_arguments(args, heap);
+        let mut shadowed: Shadowed = Vec::new();
+        heap.with_hook(|hook, heap| hook.enter_procedure(&self.name, heap));
+        let mut tail_call = self.run_frame(args_value, heap, &mut shadowed);
+        while let Some(TailCall { callee, args }) = tail_call {
+            let callable: Rc<dyn Callable> = heap.get_callable(&callee).clone();
+            let procedure = callable
+                .as_procedure()
+                .expect("Only procedures can be called in tail position");
+            heap.with_hook(|hook, heap| hook.tail_call(&procedure.name, heap));
+            tail_call = procedure.run_frame(args, heap, &mut shadowed);
         }
-        self.recovry_conflicted_variable(heap, buf);
+        heap.restore_arguments(shadowed);
+        heap.with_hook(|hook, heap| hook.exit_procedure(heap));
         None
     }
+    fn as_procedure(&self) -> Option<&Procedure> {
+        Some(self)
+    }
 }
 impl Procedure {
-    pub fn new(arguments: Vec<String>, body: Vec<Statement>) -> Self {
-        Self { arguments, body }
-    }
-    fn handel_variable_conflicts(&self, heap: &mut Heap) -> HashMap<String, Literal> {
-        //
-        let mut buf: HashMap<String, Literal> = HashMap::new();
-        for arg in self.arguments.iter() {
-            let value = heap.mut_variables().remove(arg);
-            if let Some(value) = value {
-                buf.insert(arg.clone(), value);
-            }
+    pub fn new(name: String, arguments: Vec<Slot>, body: Vec<Statement>) -> Self {
+        Self {
+            name,
+            arguments,
+            body,
         }
-        buf
     }
-    fn recovry_conflicted_variable(&self, heap: &mut Heap, buf: HashMap<String, Literal>) {
-        for (key, value) in buf.iter() {
-            heap.mut_variables().insert(key.clone(), value.clone());
-        }
+    pub fn arguments(&self) -> &Vec<Slot> {
+        &self.arguments
     }
-    fn push_arguments(&self, heap: &mut Heap, args: &[Literal]) {
-        for (arg, value) in self.arguments.iter().zip(args.iter()) {
-            heap.mut_variables().insert(arg.clone(), value.clone());
+    fn run_frame(
+        &self,
+        args: Vec<Literal>,
+        heap: &mut Heap,
+        shadowed: &mut Shadowed,
+    ) -> Option<TailCall> {
+        heap.bind_arguments(&self.arguments, args, shadowed);
+        execute_tail(&self.body, heap)
+    }
+}
+
+fn evaluate_arguments(args: &[Value], heap: &mut Heap) -> Vec<Literal> {
+    args.iter()
+        .map(|value| {
+            value
+                .expect_literal_r(heap)
+                .expect("It cannot be convert to Literal Value")
+        })
+        .collect()
+}
+
+/// Executes a block whose last statement is in tail position. A procedure
+/// call there, or at the end of a trailing `IF`, is handed back to the caller
+/// instead of being executed on top of the current frame.
+fn execute_tail(statements: &[Statement], heap: &mut Heap) -> Option<TailCall> {
+    let (last, rest) = statements.split_last()?;
+    for statement in rest {
+        statement.execute(heap);
+    }
+    match last {
+        Statement::CallExpression(call) if matches!(call.callee(), Callee::Procedure(_)) => {
+            heap.enter_statement(last);
+            Some(TailCall {
+                callee: call.callee().clone(),
+                args: evaluate_arguments(call.arguments(), heap),
+            })
+        }
+        Statement::If(if_statement) => {
+            heap.enter_statement(last);
+            if if_statement.holds(heap) {
+                execute_tail(if_statement.consequent(), heap)
+            } else {
+                None
+            }
+        }
+        statement => {
+            statement.execute(heap);
+            None
         }
     }
 }
@@ -35,3 +35,11 @@ impl Callable for ColorCommand {
         Some(Literal::Int(heap.turtle().color()))
     }
 }
+
+pub struct PenSizeCommand {}
+
+impl Callable for PenSizeCommand {
+    fn call(&self, args: &[Value], heap: &mut Heap) -> Option<Literal> {
+        Some(Literal::Float(heap.turtle().pen_size()))
+    }
+}
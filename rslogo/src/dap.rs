@@ -0,0 +1,471 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    fs,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use serde_json::{json, Value as Json};
+use unsvg::Image;
+
+use crate::{
+    ast::Statement,
+    debugger::{Stepper, Stop},
+    heap::Heap,
+    hooks::ExecutionHook,
+    panics::{catch_quietly, silence_caught_panics},
+    program::Program,
+    transport::Transport,
+};
+
+const THREAD_ID: i64 = 1;
+const VARIABLES_REFERENCE: i64 = 1;
+const TURTLE_REFERENCE: i64 = 2;
+/// Arguments of stack frame `n` are listed under `ARGUMENTS_REFERENCE + n`.
+const ARGUMENTS_REFERENCE: i64 = 3;
+
+/// Sends Debug Adapter Protocol responses and events, numbering each message.
+struct Client<R: BufRead, W: Write> {
+    transport: Transport<R, W>,
+    seq: i64,
+}
+
+impl<R: BufRead, W: Write> Client<R, W> {
+    fn new(input: R, output: W) -> Client<R, W> {
+        Client {
+            transport: Transport::new(input, output),
+            seq: 0,
+        }
+    }
+
+    fn receive(&mut self) -> io::Result<Option<Json>> {
+        self.transport.receive()
+    }
+
+    fn send(&mut self, mut message: Json) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        self.transport.send(&message)
+    }
+
+    fn respond(&mut self, request: &Json, body: Json) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "success": true,
+            "command": request["command"],
+            "body": body,
+        }))
+    }
+
+    fn fail(&mut self, request: &Json, message: &str) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "success": false,
+            "command": request["command"],
+            "message": message,
+        }))
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+        self.send(json!({
+            "type": "event",
+            "event": event,
+            "body": body,
+        }))
+    }
+}
+
+/// What the `launch` request asked for.
+struct Launch {
+    program: PathBuf,
+    image: Option<PathBuf>,
+    width: u32,
+    height: u32,
+}
+
+/// State shared by the request loop and the hook that pauses the interpreter.
+struct Session<R: BufRead, W: Write> {
+    client: Client<R, W>,
+    stepper: Stepper,
+    launch: Option<Launch>,
+    /// Lines a statement starts on, where a breakpoint can be verified.
+    lines: HashSet<usize>,
+    configured: bool,
+    disconnected: bool,
+}
+
+impl<R: BufRead, W: Write> Session<R, W> {
+    fn new(input: R, output: W) -> Session<R, W> {
+        Session {
+            client: Client::new(input, output),
+            stepper: Stepper::new(false),
+            launch: None,
+            lines: HashSet::new(),
+            configured: false,
+            disconnected: false,
+        }
+    }
+
+    fn source_path(&self) -> String {
+        self.launch
+            .as_ref()
+            .map(|launch| launch.program.display().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Handles requests until the program may run: after `configurationDone`,
+    /// or `None` if the client disconnects first.
+    fn configure(&mut self) -> io::Result<Option<Program>> {
+        let mut program = None;
+        while !self.configured {
+            let Some(request) = self.client.receive()? else {
+                return Ok(None);
+            };
+            match request["command"].as_str().unwrap_or_default() {
+                "launch" => match self.launch(&request) {
+                    Ok(launched) => {
+                        program = Some(launched);
+                        self.client.respond(&request, json!({}))?;
+                    }
+                    Err(message) => self.client.fail(&request, &message)?,
+                },
+                _ => self.handle(&request, None)?,
+            }
+            if self.disconnected {
+                return Ok(None);
+            }
+        }
+        Ok(program)
+    }
+
+    fn launch(&mut self, request: &Json) -> Result<Program, String> {
+        let arguments = &request["arguments"];
+        let path = arguments["program"]
+            .as_str()
+            .ok_or("launch expects a program path")?;
+        let source = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let size = |key: &str| arguments[key].as_u64().unwrap_or(500) as u32;
+        let launch = Launch {
+            program: PathBuf::from(path),
+            image: arguments["image"].as_str().map(PathBuf::from),
+            width: size("width"),
+            height: size("height"),
+        };
+        let image = Image::new(launch.width, launch.height);
+        let program = catch_quietly(|| Program::parse(&source, image))?;
+        self.lines.clear();
+        collect_lines(program.statements(), &mut self.lines);
+        if arguments["stopOnEntry"].as_bool().unwrap_or(false) {
+            self.stepper.step();
+        }
+        self.launch = Some(launch);
+        Ok(program)
+    }
+
+    /// Handles one request. `heap` is available while the program is paused.
+    fn handle(&mut self, request: &Json, heap: Option<&Heap>) -> io::Result<()> {
+        let arguments = &request["arguments"];
+        match request["command"].as_str().unwrap_or_default() {
+            "initialize" => {
+                self.client.respond(
+                    request,
+                    json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsFunctionBreakpoints": true,
+                    }),
+                )?;
+                self.client.event("initialized", json!({}))?;
+            }
+            "setBreakpoints" => {
+                let lines: Vec<usize> = arguments["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|breakpoint| breakpoint["line"].as_u64())
+                    .map(|line| line as usize)
+                    .collect();
+                self.stepper.set_line_breakpoints(&lines);
+                let breakpoints: Vec<Json> = lines
+                    .iter()
+                    .map(|line| json!({ "verified": self.lines.contains(line), "line": line }))
+                    .collect();
+                self.client
+                    .respond(request, json!({ "breakpoints": breakpoints }))?;
+            }
+            "setFunctionBreakpoints" => {
+                let names: Vec<String> = arguments["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|breakpoint| breakpoint["name"].as_str())
+                    .map(String::from)
+                    .collect();
+                self.stepper.set_procedure_breakpoints(&names);
+                let breakpoints: Vec<Json> =
+                    names.iter().map(|_| json!({ "verified": true })).collect();
+                self.client
+                    .respond(request, json!({ "breakpoints": breakpoints }))?;
+            }
+            "configurationDone" => {
+                self.configured = true;
+                self.client.respond(request, json!({}))?;
+            }
+            "threads" => {
+                self.client.respond(
+                    request,
+                    json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }),
+                )?;
+            }
+            "stackTrace" => {
+                let path = self.source_path();
+                let frames: Vec<Json> = self
+                    .stepper
+                    .frames()
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(id, frame)| {
+                        json!({
+                            "id": id,
+                            "name": frame.name(),
+                            "line": frame.line(),
+                            "column": 1,
+                            "source": { "path": path },
+                        })
+                    })
+                    .collect();
+                let total = frames.len();
+                self.client.respond(
+                    request,
+                    json!({ "stackFrames": frames, "totalFrames": total }),
+                )?;
+            }
+            "scopes" => {
+                let frame = arguments["frameId"].as_i64().unwrap_or_default();
+                let mut scopes = vec![
+                    json!({ "name": "Variables", "variablesReference": VARIABLES_REFERENCE, "expensive": false }),
+                    json!({ "name": "Turtle", "variablesReference": TURTLE_REFERENCE, "expensive": false }),
+                ];
+                if frame > 0 {
+                    scopes.insert(
+                        0,
+                        json!({ "name": "Arguments", "variablesReference": ARGUMENTS_REFERENCE + frame, "expensive": false }),
+                    );
+                }
+                self.client.respond(request, json!({ "scopes": scopes }))?;
+            }
+            "variables" => {
+                let reference = arguments["variablesReference"].as_i64().unwrap_or_default();
+                let variables = match heap {
+                    Some(heap) => self.variables(reference, heap),
+                    None => Vec::new(),
+                };
+                self.client
+                    .respond(request, json!({ "variables": variables }))?;
+            }
+            "continue" => {
+                self.stepper.resume();
+                self.client
+                    .respond(request, json!({ "allThreadsContinued": true }))?;
+            }
+            "next" => {
+                self.stepper.next();
+                self.client.respond(request, json!({}))?;
+            }
+            "stepIn" => {
+                self.stepper.step();
+                self.client.respond(request, json!({}))?;
+            }
+            "stepOut" => {
+                self.stepper.finish();
+                self.client.respond(request, json!({}))?;
+            }
+            "disconnect" | "terminate" => {
+                self.stepper.detach();
+                self.disconnected = true;
+                self.client.respond(request, json!({}))?;
+            }
+            command => {
+                self.client
+                    .fail(request, &format!("Unsupported request {}", command))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn variables(&self, reference: i64, heap: &Heap) -> Vec<Json> {
+        let variable = |name: &str, value: String| json!({ "name": name, "value": value, "variablesReference": 0 });
+        match reference {
+            VARIABLES_REFERENCE => {
+                let mut variables: Vec<_> = heap.variables().collect();
+                variables.sort_by(|left, right| left.0.cmp(right.0));
+                variables
+                    .into_iter()
+                    .map(|(name, value)| variable(name, value.to_string()))
+                    .collect()
+            }
+            TURTLE_REFERENCE => {
+                let turtle = heap.turtle();
+                vec![
+                    variable("x", turtle.x().to_string()),
+                    variable("y", turtle.y().to_string()),
+                    variable("direction", turtle.direction().to_string()),
+                    variable("pen", if turtle.pen() { "down" } else { "up" }.to_string()),
+                    variable("color", turtle.color().to_string()),
+                ]
+            }
+            _ => {
+                let index = (reference - ARGUMENTS_REFERENCE) as usize;
+                let Some(frame) = self.stepper.frames().get(index) else {
+                    return Vec::new();
+                };
+                let Some(procedure) = heap
+                    .procedures()
+                    .find(|(name, _)| *name == frame.name())
+                    .and_then(|(_, callable)| callable.as_procedure())
+                else {
+                    return Vec::new();
+                };
+                procedure
+                    .arguments()
+                    .iter()
+                    .map(|slot| {
+                        let value = self
+                            .stepper
+                            .frame_variable(index, *slot, heap)
+                            .map(|value| value.to_string())
+                            .unwrap_or_default();
+                        variable(heap.variable_name(*slot), value)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Reports the stop and serves requests until one resumes the program.
+    fn pause(&mut self, stop: Stop, heap: &Heap) -> io::Result<()> {
+        let reason = match stop {
+            Stop::Breakpoint => "breakpoint",
+            Stop::Step => "step",
+        };
+        self.client.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        )?;
+        loop {
+            let Some(request) = self.client.receive()? else {
+                self.stepper.detach();
+                return Ok(());
+            };
+            self.handle(&request, Some(heap))?;
+            let command = request["command"].as_str().unwrap_or_default();
+            if matches!(
+                command,
+                "continue" | "next" | "stepIn" | "stepOut" | "disconnect" | "terminate"
+            ) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Lets the session stop the interpreter before statements.
+struct SessionHook<R: BufRead, W: Write> {
+    session: Rc<RefCell<Session<R, W>>>,
+}
+
+impl<R: BufRead, W: Write> ExecutionHook for SessionHook<R, W> {
+    fn before_statement(&mut self, statement: &Statement, heap: &Heap) {
+        let mut session = self.session.borrow_mut();
+        if let Some(stop) = session.stepper.before_statement(statement.span().start()) {
+            session
+                .pause(stop, heap)
+                .expect("Failed to talk to the debug client");
+        }
+        if session.disconnected {
+            panic!("Debug session disconnected");
+        }
+    }
+
+    fn enter_procedure(&mut self, name: &str, heap: &Heap) {
+        self.session
+            .borrow_mut()
+            .stepper
+            .enter_procedure(name, heap);
+    }
+
+    fn tail_call(&mut self, name: &str, heap: &Heap) {
+        self.session.borrow_mut().stepper.tail_call(name, heap);
+    }
+
+    fn exit_procedure(&mut self, _heap: &Heap) {
+        self.session.borrow_mut().stepper.exit_procedure();
+    }
+}
+
+/// Runs a Debug Adapter Protocol session. Runtime errors are reported to the
+/// client as output instead of ending the process.
+pub fn serve<R: BufRead + 'static, W: Write + 'static>(input: R, output: W) -> io::Result<()> {
+    silence_caught_panics();
+    let session = Rc::new(RefCell::new(Session::new(input, output)));
+    let program = session.borrow_mut().configure()?;
+    if let Some(mut program) = program {
+        program.set_hook(Box::new(SessionHook {
+            session: session.clone(),
+        }));
+        let result = catch_quietly(|| program.run());
+        let mut session = session.borrow_mut();
+        let exit_code = if result.is_ok() { 0 } else { 1 };
+        if let Err(message) = result {
+            if !session.disconnected {
+                let message = format!("Error: {}\n", message);
+                session
+                    .client
+                    .event("output", json!({ "category": "stderr", "output": message }))?;
+            }
+        }
+        if let Some(path) = session
+            .launch
+            .as_ref()
+            .and_then(|launch| launch.image.clone())
+        {
+            if let Err(message) = program.save_image(Path::new(&path)) {
+                session.client.event(
+                    "output",
+                    json!({ "category": "stderr", "output": format!("{}\n", message) }),
+                )?;
+            }
+        }
+        if !session.disconnected {
+            session
+                .client
+                .event("exited", json!({ "exitCode": exit_code }))?;
+            session.client.event("terminated", json!({}))?;
+        }
+    }
+    // Serve whatever the client still asks, typically `disconnect`.
+    let mut session = session.borrow_mut();
+    while !session.disconnected {
+        let Some(request) = session.client.receive()? else {
+            break;
+        };
+        session.handle(&request, None)?;
+    }
+    Ok(())
+}
+
+fn collect_lines(statements: &[Statement], lines: &mut HashSet<usize>) {
+    for statement in statements {
+        lines.insert(statement.span().start());
+        match statement {
+            Statement::If(if_statement) => collect_lines(if_statement.consequent(), lines),
+            Statement::While(while_statement) => collect_lines(while_statement.body(), lines),
+            Statement::ProcedureDeclaration(procedure) => collect_lines(procedure.body(), lines),
+            Statement::CallExpression(_) | Statement::AssignmentExpression(_) => {}
+        }
+    }
+}
@@ -0,0 +1,445 @@
+use std::{
+    fmt::Display,
+    io::{BufRead, Write},
+};
+
+use crate::{
+    ast::Statement,
+    commands::Literal,
+    heap::{Heap, Shadowed, Slot},
+    hooks::ExecutionHook,
+};
+
+const HELP: &str = "\
+step, s             run until the next statement, entering procedures
+next, n             run until the next statement in this procedure or its caller
+finish, f           run until the current procedure returns
+continue, c         run until a breakpoint is hit
+break, b <at>       stop at a line number or on entry to a procedure
+delete, d <at>      remove a breakpoint
+print, p [name]     print one variable, or all of them
+turtle, t           print the turtle state
+backtrace, bt       print the procedure call stack
+list, l             print the source around the current line
+quit, q             stop the program";
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    Line(usize),
+    Procedure(String),
+}
+
+impl Breakpoint {
+    pub fn parse(at: &str) -> Breakpoint {
+        match at.parse::<usize>() {
+            Ok(line) => Breakpoint::Line(line),
+            Err(_) => Breakpoint::Procedure(at.trim_start_matches('"').to_string()),
+        }
+    }
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Breakpoint::Line(line) => write!(f, "line {}", line),
+            Breakpoint::Procedure(name) => write!(f, "procedure {}", name),
+        }
+    }
+}
+
+/// How far to run before stopping again. Depths are call stack lengths.
+enum Mode {
+    Step,
+    Next(usize),
+    Finish(usize),
+    Continue,
+    Detached,
+}
+
+/// Why execution stopped before a statement.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    Step,
+    Breakpoint,
+}
+
+pub struct Frame {
+    name: String,
+    line: usize,
+    /// The values this frame's parameters hid when it was entered.
+    shadowed: Shadowed,
+}
+
+impl Frame {
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+/// Breakpoints, stepping mode and the procedure call stack, shared by the
+/// terminal debugger and the debug adapter.
+pub struct Stepper {
+    breakpoints: Vec<Breakpoint>,
+    mode: Mode,
+    frames: Vec<Frame>,
+    entered_breakpoint: bool,
+}
+
+#[allow(dead_code)]
+impl Stepper {
+    pub fn new(stop_on_entry: bool) -> Stepper {
+        Stepper {
+            breakpoints: Vec::new(),
+            mode: if stop_on_entry {
+                Mode::Step
+            } else {
+                Mode::Continue
+            },
+            frames: vec![Frame {
+                name: "main".to_string(),
+                line: 0,
+                shadowed: Vec::new(),
+            }],
+            entered_breakpoint: false,
+        }
+    }
+    pub fn frames(&self) -> &Vec<Frame> {
+        &self.frames
+    }
+    pub fn breakpoints(&self) -> &Vec<Breakpoint> {
+        &self.breakpoints
+    }
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+    pub fn remove_breakpoint(&mut self, breakpoint: &Breakpoint) {
+        self.breakpoints.retain(|other| other != breakpoint);
+    }
+    /// Replaces every line breakpoint, keeping procedure breakpoints.
+    pub fn set_line_breakpoints(&mut self, lines: &[usize]) {
+        self.breakpoints
+            .retain(|breakpoint| matches!(breakpoint, Breakpoint::Procedure(_)));
+        self.breakpoints
+            .extend(lines.iter().map(|line| Breakpoint::Line(*line)));
+    }
+    /// Replaces every procedure breakpoint, keeping line breakpoints.
+    pub fn set_procedure_breakpoints(&mut self, names: &[String]) {
+        self.breakpoints
+            .retain(|breakpoint| matches!(breakpoint, Breakpoint::Line(_)));
+        self.breakpoints
+            .extend(names.iter().cloned().map(Breakpoint::Procedure));
+    }
+    pub fn step(&mut self) {
+        self.mode = Mode::Step;
+    }
+    pub fn next(&mut self) {
+        self.mode = Mode::Next(self.frames.len());
+    }
+    pub fn finish(&mut self) {
+        self.mode = Mode::Finish(self.frames.len());
+    }
+    pub fn resume(&mut self) {
+        self.mode = Mode::Continue;
+    }
+    /// Runs to the end, ignoring breakpoints.
+    pub fn detach(&mut self) {
+        self.mode = Mode::Detached;
+    }
+
+    /// Records the statement about to run and decides whether to stop there.
+    pub fn before_statement(&mut self, line: usize) -> Option<Stop> {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.line = line;
+        }
+        let depth = self.frames.len();
+        let stepped = match self.mode {
+            Mode::Step => true,
+            Mode::Next(at) => depth <= at,
+            Mode::Finish(at) => depth < at,
+            Mode::Continue => false,
+            Mode::Detached => return None,
+        };
+        let hit = std::mem::take(&mut self.entered_breakpoint)
+            || self.breakpoints.contains(&Breakpoint::Line(line));
+        if hit {
+            Some(Stop::Breakpoint)
+        } else if stepped {
+            Some(Stop::Step)
+        } else {
+            None
+        }
+    }
+    pub fn enter_procedure(&mut self, name: &str, heap: &Heap) {
+        self.frames.push(Frame {
+            name: name.to_string(),
+            line: 0,
+            shadowed: Vec::new(),
+        });
+        self.bind(name, heap);
+    }
+    /// Reuses the current frame. Like `Heap::bind_arguments`, parameters it
+    /// already shadows keep the first value they hid.
+    pub fn tail_call(&mut self, name: &str, heap: &Heap) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.name = name.to_string();
+            frame.line = 0;
+        }
+        self.bind(name, heap);
+    }
+    fn bind(&mut self, name: &str, heap: &Heap) {
+        let slots = heap
+            .procedures()
+            .find(|(procedure, _)| *procedure == name)
+            .and_then(|(_, callable)| callable.as_procedure())
+            .map(|procedure| procedure.arguments().clone())
+            .unwrap_or_default();
+        if let Some(frame) = self.frames.last_mut() {
+            for slot in slots {
+                let value = heap.variable(slot).cloned();
+                match frame
+                    .shadowed
+                    .iter_mut()
+                    .find(|(shadowed, _)| *shadowed == slot)
+                {
+                    Some((_, saved)) => {
+                        if saved.is_none() {
+                            *saved = value;
+                        }
+                    }
+                    None => frame.shadowed.push((slot, value)),
+                }
+            }
+        }
+        self.entered_breakpoint = self
+            .breakpoints
+            .contains(&Breakpoint::Procedure(name.to_string()));
+    }
+    pub fn exit_procedure(&mut self) {
+        self.frames.pop();
+        self.entered_breakpoint = false;
+    }
+    /// The value `slot` has in the frame at `index`. Calls made from that frame
+    /// overwrite the parameters they share with it, so those come from what the
+    /// first such call shadowed.
+    pub fn frame_variable<'a>(
+        &'a self,
+        index: usize,
+        slot: Slot,
+        heap: &'a Heap,
+    ) -> Option<&'a Literal> {
+        self.frames
+            .iter()
+            .skip(index + 1)
+            .find_map(|frame| {
+                frame
+                    .shadowed
+                    .iter()
+                    .find(|(shadowed, _)| *shadowed == slot)
+            })
+            .map_or_else(|| heap.variable(slot), |(_, value)| value.as_ref())
+    }
+}
+
+/// A terminal debugger that pauses the interpreter before statements and reads
+/// commands from `input` until told to resume.
+pub struct Debugger<R: BufRead, W: Write> {
+    source: Vec<String>,
+    input: R,
+    output: W,
+    stepper: Stepper,
+    last_command: String,
+}
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+    /// The debugger starts paused on the first statement.
+    pub fn new(source: &str, input: R, output: W) -> Debugger<R, W> {
+        Debugger {
+            source: source.lines().map(String::from).collect(),
+            input,
+            output,
+            stepper: Stepper::new(true),
+            last_command: String::new(),
+        }
+    }
+
+    fn say(&mut self, text: impl Display) {
+        writeln!(self.output, "{}", text).expect("Failed to write debugger output");
+    }
+
+    fn source_line(&self, line: usize) -> &str {
+        line.checked_sub(1)
+            .and_then(|index| self.source.get(index))
+            .map(|text| text.trim())
+            .unwrap_or_default()
+    }
+
+    fn pause(&mut self, line: usize, heap: &Heap) {
+        let frame = self.stepper.frames().last().expect("Call stack is empty");
+        let location = format!(
+            "Stopped at line {} in {}: {}",
+            line,
+            frame.name,
+            self.source_line(line)
+        );
+        self.say(location);
+        loop {
+            write!(self.output, "(debug) ").expect("Failed to write debugger output");
+            self.output
+                .flush()
+                .expect("Failed to write debugger output");
+            let mut command = String::new();
+            let read = self
+                .input
+                .read_line(&mut command)
+                .expect("Failed to read debugger input");
+            if read == 0 {
+                self.stepper.detach();
+                return;
+            }
+            let mut command = command.trim().to_string();
+            if command.is_empty() {
+                command = self.last_command.clone();
+            } else {
+                self.last_command = command.clone();
+            }
+            if self.command(&command, line, heap) {
+                return;
+            }
+        }
+    }
+
+    /// Runs one debugger command; returns `true` when the program should resume.
+    fn command(&mut self, command: &str, line: usize, heap: &Heap) -> bool {
+        let mut words = command.split_ascii_whitespace();
+        match words.next().unwrap_or_default() {
+            "" => {}
+            "step" | "s" => {
+                self.stepper.step();
+                return true;
+            }
+            "next" | "n" => {
+                self.stepper.next();
+                return true;
+            }
+            "finish" | "f" => {
+                self.stepper.finish();
+                return true;
+            }
+            "continue" | "c" => {
+                self.stepper.resume();
+                return true;
+            }
+            "quit" | "q" => std::process::exit(0),
+            "break" | "b" => match words.next() {
+                Some(at) => {
+                    let breakpoint = Breakpoint::parse(at);
+                    self.say(format!("Breakpoint at {}", breakpoint));
+                    self.stepper.add_breakpoint(breakpoint);
+                }
+                None => {
+                    let breakpoints: Vec<String> = self
+                        .stepper
+                        .breakpoints()
+                        .iter()
+                        .map(|breakpoint| format!("Breakpoint at {}", breakpoint))
+                        .collect();
+                    for breakpoint in breakpoints {
+                        self.say(breakpoint);
+                    }
+                }
+            },
+            "delete" | "d" => match words.next() {
+                Some(at) => {
+                    let breakpoint = Breakpoint::parse(at);
+                    self.stepper.remove_breakpoint(&breakpoint);
+                    self.say(format!("Deleted breakpoint at {}", breakpoint));
+                }
+                None => self.say("Error: delete expects a line number or procedure name"),
+            },
+            "print" | "p" => match words.next() {
+                Some(name) => {
+                    let name = name.trim_start_matches([':', '"']);
+                    match heap.variable_value(name) {
+                        Some(value) => self.say(format!("{} = {}", name, value)),
+                        None => self.say(format!("{} is not defined", name)),
+                    }
+                }
+                None => {
+                    let mut variables: Vec<String> = heap
+                        .variables()
+                        .map(|(name, value)| format!("{} = {}", name, value))
+                        .collect();
+                    variables.sort();
+                    for variable in variables {
+                        self.say(variable);
+                    }
+                }
+            },
+            "turtle" | "t" => {
+                let turtle = heap.turtle();
+                let state = format!(
+                    "x = {} y = {} direction = {} pen = {} color = {}",
+                    turtle.x(),
+                    turtle.y(),
+                    turtle.direction(),
+                    if turtle.pen() { "down" } else { "up" },
+                    turtle.color()
+                );
+                self.say(state);
+            }
+            "backtrace" | "bt" => {
+                let frames: Vec<String> = self
+                    .stepper
+                    .frames()
+                    .iter()
+                    .rev()
+                    .enumerate()
+                    .map(|(index, frame)| {
+                        format!("#{} {} at line {}", index, frame.name, frame.line)
+                    })
+                    .collect();
+                for frame in frames {
+                    self.say(frame);
+                }
+            }
+            "list" | "l" => {
+                let first = line.saturating_sub(3).max(1);
+                let last = (line + 3).min(self.source.len());
+                for number in first..=last {
+                    let marker = if number == line { "=>" } else { "  " };
+                    let text = format!("{} {:>4} {}", marker, number, self.source[number - 1]);
+                    self.say(text);
+                }
+            }
+            "help" | "h" => self.say(HELP),
+            command => self.say(format!("Error: unknown command {}, try help", command)),
+        }
+        false
+    }
+}
+
+impl<R: BufRead, W: Write> ExecutionHook for Debugger<R, W> {
+    fn before_statement(&mut self, statement: &Statement, heap: &Heap) {
+        let line = statement.span().start();
+        if self.stepper.before_statement(line).is_some() {
+            self.pause(line, heap);
+        }
+    }
+
+    fn enter_procedure(&mut self, name: &str, heap: &Heap) {
+        self.stepper.enter_procedure(name, heap);
+    }
+
+    fn tail_call(&mut self, name: &str, heap: &Heap) {
+        self.stepper.tail_call(name, heap);
+    }
+
+    fn exit_procedure(&mut self, _heap: &Heap) {
+        self.stepper.exit_procedure();
+    }
+}
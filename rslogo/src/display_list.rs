@@ -0,0 +1,308 @@
+use serde::Serialize;
+use unsvg::Color;
+
+use crate::font;
+
+/// A colour by its channels, independent of any palette.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Rgb {
+    red: u8,
+    green: u8,
+    blue: u8,
+}
+
+#[allow(dead_code)]
+impl Rgb {
+    pub fn new(red: u8, green: u8, blue: u8) -> Rgb {
+        Rgb { red, green, blue }
+    }
+    /// Checks channels that came from a Logo program.
+    pub fn from_channels(red: i32, green: i32, blue: i32) -> Rgb {
+        let channel = |value: i32| {
+            u8::try_from(value).unwrap_or_else(|_| {
+                panic!("Error: colour channels go from 0 to 255, got {}", value)
+            })
+        };
+        Rgb::new(channel(red), channel(green), channel(blue))
+    }
+    pub fn red(&self) -> u8 {
+        self.red
+    }
+    pub fn green(&self) -> u8 {
+        self.green
+    }
+    pub fn blue(&self) -> u8 {
+        self.blue
+    }
+    /// `#rrggbb`, as SVG and CSS write colours.
+    pub fn hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+}
+
+impl From<Color> for Rgb {
+    fn from(color: Color) -> Rgb {
+        Rgb::new(color.red, color.green, color.blue)
+    }
+}
+
+impl From<Rgb> for Color {
+    fn from(rgb: Rgb) -> Color {
+        Color::new_rgb(rgb.red, rgb.green, rgb.blue)
+    }
+}
+
+/// A straight stroke between two points.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Segment {
+    from: (f32, f32),
+    to: (f32, f32),
+    color: Rgb,
+    width: f32,
+}
+
+#[allow(dead_code)]
+impl Segment {
+    pub fn new(from: (f32, f32), to: (f32, f32), color: Rgb, width: f32) -> Segment {
+        Segment {
+            from,
+            to,
+            color,
+            width,
+        }
+    }
+    pub fn from(&self) -> (f32, f32) {
+        self.from
+    }
+    pub fn to(&self) -> (f32, f32) {
+        self.to
+    }
+    pub fn color(&self) -> Rgb {
+        self.color
+    }
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+}
+
+/// Part of a circle around `center`. `start` is the heading the arc begins
+/// at and `sweep` runs clockwise from it, both in degrees, so a sweep of 360
+/// or more is a whole circle.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Arc {
+    center: (f32, f32),
+    radius: f32,
+    start: f32,
+    sweep: f32,
+    color: Rgb,
+    width: f32,
+}
+
+#[allow(dead_code)]
+impl Arc {
+    pub fn new(
+        center: (f32, f32),
+        radius: f32,
+        start: f32,
+        sweep: f32,
+        color: Rgb,
+        width: f32,
+    ) -> Arc {
+        Arc {
+            center,
+            radius,
+            start,
+            sweep,
+            color,
+            width,
+        }
+    }
+    pub fn center(&self) -> (f32, f32) {
+        self.center
+    }
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+    pub fn start(&self) -> f32 {
+        self.start
+    }
+    pub fn sweep(&self) -> f32 {
+        self.sweep
+    }
+    pub fn color(&self) -> Rgb {
+        self.color
+    }
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+    pub fn is_circle(&self) -> bool {
+        self.sweep.abs() >= 360.0
+    }
+    /// The point at `degrees` clockwise from `start`.
+    pub fn point(&self, degrees: f32) -> (f32, f32) {
+        let radians = (self.start + degrees).to_radians();
+        (
+            self.center.0 + self.radius * radians.sin(),
+            self.center.1 - self.radius * radians.cos(),
+        )
+    }
+}
+
+/// A filled disc the size of the pen.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Dot {
+    center: (f32, f32),
+    diameter: f32,
+    color: Rgb,
+}
+
+#[allow(dead_code)]
+impl Dot {
+    pub fn new(center: (f32, f32), diameter: f32, color: Rgb) -> Dot {
+        Dot {
+            center,
+            diameter,
+            color,
+        }
+    }
+    pub fn center(&self) -> (f32, f32) {
+        self.center
+    }
+    pub fn diameter(&self) -> f32 {
+        self.diameter
+    }
+    pub fn color(&self) -> Rgb {
+        self.color
+    }
+}
+
+/// A polygon through the points the turtle visited while filling.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Fill {
+    points: Vec<(f32, f32)>,
+    color: Rgb,
+}
+
+#[allow(dead_code)]
+impl Fill {
+    pub fn new(points: Vec<(f32, f32)>, color: Rgb) -> Fill {
+        Fill { points, color }
+    }
+    pub fn points(&self) -> &Vec<(f32, f32)> {
+        &self.points
+    }
+    pub fn color(&self) -> Rgb {
+        self.color
+    }
+}
+
+/// Text written from `position`, the start of its baseline. The text is
+/// rotated with the turtle, so it reads left to right at heading 0.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Label {
+    position: (f32, f32),
+    heading: f32,
+    height: f32,
+    text: String,
+    color: Rgb,
+}
+
+#[allow(dead_code)]
+impl Label {
+    pub fn new(position: (f32, f32), heading: f32, height: f32, text: String, color: Rgb) -> Label {
+        Label {
+            position,
+            heading,
+            height,
+            text,
+            color,
+        }
+    }
+    pub fn position(&self) -> (f32, f32) {
+        self.position
+    }
+    pub fn heading(&self) -> f32 {
+        self.heading
+    }
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+    pub fn text(&self) -> &String {
+        &self.text
+    }
+    pub fn color(&self) -> Rgb {
+        self.color
+    }
+    /// The text in the built-in stroke font, placed and rotated.
+    pub fn strokes(&self) -> Vec<Vec<(f32, f32)>> {
+        let (sin, cos) = self.heading.to_radians().sin_cos();
+        font::outline(&self.text, self.height)
+            .into_iter()
+            .map(|stroke| {
+                stroke
+                    .into_iter()
+                    .map(|(x, y)| {
+                        (
+                            self.position.0 + x * cos - y * sin,
+                            self.position.1 + x * sin + y * cos,
+                        )
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Something the turtle drew, in canvas coordinates.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Primitive {
+    Segment(Segment),
+    Arc(Arc),
+    Dot(Dot),
+    Fill(Fill),
+    Label(Label),
+}
+
+/// Everything a run drew, in drawing order. The turtle records it whatever
+/// canvas it draws on, so exporters and tests can work from exact geometry
+/// rather than image bytes.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DisplayList {
+    width: u32,
+    height: u32,
+    primitives: Vec<Primitive>,
+}
+
+#[allow(dead_code)]
+impl DisplayList {
+    pub fn new(width: u32, height: u32) -> DisplayList {
+        DisplayList {
+            width,
+            height,
+            primitives: Vec::new(),
+        }
+    }
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+    pub fn primitives(&self) -> &Vec<Primitive> {
+        &self.primitives
+    }
+    pub fn push(&mut self, primitive: Primitive) {
+        self.primitives.push(primitive);
+    }
+    pub fn insert(&mut self, index: usize, primitive: Primitive) {
+        self.primitives.insert(index, primitive);
+    }
+    pub fn segments(&self) -> impl Iterator<Item = &Segment> {
+        self.primitives
+            .iter()
+            .filter_map(|primitive| match primitive {
+                Primitive::Segment(segment) => Some(segment),
+                _ => None,
+            })
+    }
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Display lists always serialize")
+    }
+}
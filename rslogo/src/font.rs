@@ -0,0 +1,126 @@
+//! A single-stroke vector font, so labels can be drawn as lines on canvases
+//! that have no fonts to render text with.
+
+/// Glyphs are drawn on a grid 4 units wide. Capitals run from row 0 down to
+/// the baseline at row 6, and commas dip to row 7. A label's height covers
+/// 9 rows, so capitals are two thirds of it, much as in a sans-serif face.
+pub const ROWS: f32 = 9.0;
+pub const BASELINE: f32 = 6.0;
+/// How far each character moves the pen along the line, in grid units.
+pub const ADVANCE: f32 = 5.0;
+
+/// Each stroke is a run of `xy` grid points, and strokes are separated by
+/// spaces. Lower-case letters use the capitals.
+const GLYPHS: [(char, &str); 68] = [
+    (' ', ""),
+    ('A', "062046 1333"),
+    ('B', "06003041423303 3344453606"),
+    ('C', "4130100105163645"),
+    ('D', "00062644422000"),
+    ('E', "40000646 0333"),
+    ('F', "400006 0333"),
+    ('G', "41301001051636454323"),
+    ('H', "0006 4046 0343"),
+    ('I', "1030 2026 1636"),
+    ('J', "4045361605"),
+    ('K', "0006 4004 1346"),
+    ('L', "000646"),
+    ('M', "0600234046"),
+    ('N', "06004640"),
+    ('O', "103041453616050110"),
+    ('P', "06003041423303"),
+    ('Q', "103041453616050110 2446"),
+    ('R', "06003041423303 2346"),
+    ('S', "413010010213334445361605"),
+    ('T', "0040 2026"),
+    ('U', "000516364540"),
+    ('V', "002640"),
+    ('W', "0016223640"),
+    ('X', "0046 4006"),
+    ('Y', "002340 2326"),
+    ('Z', "00400646"),
+    ('0', "103041453616050110 4105"),
+    ('1', "112026 1636"),
+    ('2', "01103041420646"),
+    ('3', "01103041423313 334445361605"),
+    ('4', "36300444"),
+    ('5', "400003334445361605"),
+    ('6', "30100105163645443303"),
+    ('7', "004016"),
+    ('8', "103041423313020110 1304051636454433"),
+    ('9', "43130201103041453616"),
+    ('.', "2526"),
+    (',', "252617"),
+    (':', "2122 2526"),
+    (';', "2122 252617"),
+    ('!', "2023 2526"),
+    ('?', "011030414223 2526"),
+    ('-', "0343"),
+    ('+', "0343 2125"),
+    ('*', "2125 1234 3214"),
+    ('/', "4006"),
+    ('\\', "0046"),
+    ('=', "0242 0444"),
+    ('(', "30121436"),
+    (')', "10323416"),
+    ('[', "30101636"),
+    (']', "10303616"),
+    ('<', "400346"),
+    ('>', "004306"),
+    ('_', "0747"),
+    ('\'', "2022"),
+    ('"', "1012 3032"),
+    ('#', "1016 3036 0242 0444"),
+    ('%', "4006 0011 3546"),
+    ('&', "4612112031320405162644"),
+    ('$', "413010010213334445361605 2026"),
+    ('^', "032043"),
+    ('|', "2026"),
+    ('`', "1021"),
+    ('~', "0312233443"),
+    ('{', "30212213242536"),
+    ('}', "10212233242516"),
+];
+
+/// Drawn for characters the font has no glyph for.
+const MISSING: &str = "0040460600";
+
+/// A glyph's strokes as they are written in the table.
+pub fn encoded(c: char) -> &'static str {
+    let c = c.to_ascii_uppercase();
+    GLYPHS
+        .iter()
+        .find(|(glyph, _)| *glyph == c)
+        .map_or(MISSING, |(_, strokes)| strokes)
+}
+
+/// The strokes of a glyph in grid units.
+pub fn glyph(c: char) -> Vec<Vec<(f32, f32)>> {
+    encoded(c)
+        .split_whitespace()
+        .map(|stroke| {
+            stroke
+                .as_bytes()
+                .chunks(2)
+                .map(|point| ((point[0] - b'0') as f32, (point[1] - b'0') as f32))
+                .collect()
+        })
+        .collect()
+}
+
+/// The strokes of `text` set `height` tall, from the start of its baseline
+/// with y growing down.
+pub fn outline(text: &str, height: f32) -> Vec<Vec<(f32, f32)>> {
+    let unit = height / ROWS;
+    text.chars()
+        .enumerate()
+        .flat_map(|(index, c)| {
+            glyph(c).into_iter().map(move |stroke| {
+                stroke
+                    .into_iter()
+                    .map(|(x, y)| ((index as f32 * ADVANCE + x) * unit, (y - BASELINE) * unit))
+                    .collect()
+            })
+        })
+        .collect()
+}
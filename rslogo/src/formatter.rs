@@ -0,0 +1,209 @@
+use std::{iter::Peekable, vec::IntoIter};
+
+use crate::{
+    ast::{BinaryOperation, CallExpression, Callee, Statement},
+    commands::{Literal, Value},
+    tokens::{
+        ASTParser, AssignmentType, CalculationOperation, IdentifierType, LogicalOperation,
+        TokenType, Tokenizer,
+    },
+};
+
+const INDENT: &str = "    ";
+
+/// Pretty-prints Logo source with canonical indentation. Comments stay on
+/// their own line before the statement that followed them, and runs of blank
+/// lines between statements collapse to one. Literals are written as they
+/// were in the source.
+pub struct Formatter {
+    comments: Peekable<IntoIter<(usize, String)>>,
+    literals: Peekable<IntoIter<String>>,
+    output: String,
+    depth: usize,
+    /// The last source line written, used to keep blank lines.
+    last_line: usize,
+    block_start: bool,
+}
+
+impl Formatter {
+    /// Panics like `ASTParser` when the source does not parse.
+    pub fn format(source: &String) -> String {
+        let mut tokenizer = Tokenizer::new(source);
+        tokenizer.scan();
+        let comments = tokenizer.move_comments();
+        let literals = tokenizer.move_literals();
+        let statements = ASTParser::new_from_tokens(tokenizer.move_token()).parse_statement();
+        let mut formatter = Formatter {
+            comments: comments.into_iter().peekable(),
+            literals: literals.into_iter().peekable(),
+            output: String::new(),
+            depth: 0,
+            last_line: 0,
+            block_start: true,
+        };
+        formatter.block(&statements, usize::MAX);
+        formatter.output
+    }
+
+    /// Writes a block's statements, then any comments before its closing line.
+    fn block(&mut self, statements: &[Statement], end: usize) {
+        for statement in statements {
+            self.statement(statement);
+        }
+        self.comments_before(end);
+    }
+
+    fn comments_before(&mut self, line: usize) {
+        while let Some((at, comment)) = self.comments.next_if(|(at, _)| *at < line) {
+            self.line(at, &comment);
+        }
+    }
+
+    fn line(&mut self, source_line: usize, text: &str) {
+        if !self.block_start && source_line > self.last_line + 1 {
+            self.output.push('\n');
+        }
+        self.block_start = false;
+        self.output.push_str(&INDENT.repeat(self.depth));
+        self.output.push_str(text);
+        self.output.push('\n');
+        self.last_line = self.last_line.max(source_line);
+    }
+
+    fn open(&mut self, source_line: usize, text: &str) {
+        self.line(source_line, text);
+        self.depth += 1;
+        self.block_start = true;
+    }
+
+    fn close(&mut self, source_line: usize, text: &str) {
+        self.depth -= 1;
+        self.block_start = true;
+        self.line(source_line, text);
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        let span = statement.span();
+        self.comments_before(span.start());
+        match statement {
+            Statement::CallExpression(call) => {
+                let text = self.call_expression(call);
+                self.line(span.start(), &text);
+            }
+            Statement::AssignmentExpression(assign) => {
+                let operation = match assign.operation() {
+                    AssignmentType::Make => "MAKE",
+                    AssignmentType::Addassign => "ADDASSIGN",
+                };
+                let text = format!(
+                    "{} \"{} {}",
+                    operation,
+                    assign.left(),
+                    self.value_text(assign.right())
+                );
+                self.line(span.start(), &text);
+            }
+            Statement::If(if_statement) => {
+                let text = format!("IF {} [", self.value_text(if_statement.test()));
+                self.open(span.start(), &text);
+                self.block(if_statement.consequent(), span.end());
+                self.close(span.end(), "]");
+            }
+            Statement::While(while_statement) => {
+                let text = format!("WHILE {} [", self.value_text(while_statement.test()));
+                self.open(span.start(), &text);
+                self.block(while_statement.body(), span.end());
+                self.close(span.end(), "]");
+            }
+            Statement::ProcedureDeclaration(procedure) => {
+                let mut text = format!("TO {}", procedure.name());
+                for argument in procedure.arguments() {
+                    text.push_str(&format!(" \"{}", argument));
+                }
+                self.open(span.start(), &text);
+                self.block(procedure.body(), span.end());
+                self.close(span.end(), "END");
+            }
+        }
+    }
+
+    fn call_expression(&mut self, call: &CallExpression) -> String {
+        let mut text = match call.callee() {
+            Callee::Command(command) => command.keyword().to_string(),
+            Callee::Query(query) => query.keyword().to_string(),
+            Callee::Procedure(name) => name.clone(),
+        };
+        let rgb_start = match call.callee() {
+            Callee::Command(command) => command.rgb_start(call.arguments().len()),
+            _ => None,
+        };
+        for (index, argument) in call.arguments().iter().enumerate() {
+            if Some(index) == rgb_start {
+                text.push_str(" [");
+            }
+            text.push(' ');
+            text.push_str(&self.value_text(argument));
+        }
+        if rgb_start.is_some() {
+            text.push_str(" ]");
+        }
+        text
+    }
+
+    fn value_text(&mut self, value: &Value) -> String {
+        match value {
+            Value::Literal(literal) => self.literal(literal),
+            Value::Identifier(IdentifierType::Variable(name)) => format!(":{}", name),
+            Value::Variable(variable) => format!(":{}", variable.name()),
+            Value::Identifier(IdentifierType::Procedure(name)) => name.clone(),
+            Value::Statement(call) => self.call_expression(call),
+            Value::Word(word) if !word.is_empty() && !word.contains(' ') => format!("\"{}", word),
+            Value::Word(words) => format!("[{}]", words),
+            Value::BinaryExpression(binary) => {
+                let operation = match binary.operation() {
+                    BinaryOperation::Calculation(calculation) => match calculation {
+                        CalculationOperation::Plus => "+",
+                        CalculationOperation::Dash => "-",
+                        CalculationOperation::Star => "*",
+                        CalculationOperation::Slash => "/",
+                    },
+                    BinaryOperation::Logic(logic) => match logic {
+                        LogicalOperation::Eq => "EQ",
+                        LogicalOperation::Ne => "NE",
+                        LogicalOperation::Gt => "GT",
+                        LogicalOperation::Lt => "LT",
+                        LogicalOperation::And => "AND",
+                        LogicalOperation::Or => "OR",
+                    },
+                };
+                format!(
+                    "{} {} {}",
+                    operation,
+                    self.value_text(binary.left()),
+                    self.value_text(binary.right())
+                )
+            }
+        }
+    }
+
+    /// The next literal token, unless it does not scan to `literal`.
+    fn literal(&mut self, literal: &Literal) -> String {
+        let written = self.literals.next_if(|text| {
+            matches!(Tokenizer::classify(text), TokenType::Value(value) if value == *literal)
+        });
+        written.unwrap_or_else(|| format!("\"{}", literal_text(literal)))
+    }
+}
+
+/// Keeps a float's decimal point, since `"5` would scan as an integer.
+fn literal_text(literal: &Literal) -> String {
+    match literal {
+        Literal::Int(number) => number.to_string(),
+        Literal::Float(number) if number.fract() == 0.0 && number.is_finite() => {
+            format!("{:.1}", number)
+        }
+        Literal::Float(number) => number.to_string(),
+        Literal::Bool(true) => "TRUE".to_string(),
+        Literal::Bool(false) => "FALSE".to_string(),
+    }
+}
@@ -3,33 +3,107 @@ use std::{collections::HashMap, rc::Rc};
 use unsvg::Image;
 
 use crate::{
-    ast::Callee,
+    ast::{Callee, Statement},
+    canvas::{Canvas, UnsvgCanvas},
     commands::{Assignable, AssignableFactory, Callable, CallableFactory, Literal, Value},
+    hooks::ExecutionHook,
     tokens::AssignmentType,
     turtle::Turtle,
 };
 
+pub type Slot = usize;
+/// Values hidden by the parameters of a running procedure, restored on return.
+pub type Shadowed = Vec<(Slot, Option<Literal>)>;
+
 pub struct Heap {
-    variables: HashMap<String, Literal>,
+    slots: HashMap<String, Slot>,
+    names: Vec<String>,
+    variables: Vec<Option<Literal>>,
     assignables: HashMap<AssignmentType, Rc<dyn Assignable>>,
     callables: HashMap<Callee, Rc<dyn Callable>>,
     turtle: Turtle,
+    hook: Option<Box<dyn ExecutionHook>>,
+    line: usize,
 }
 impl Heap {
     pub fn new(image: Image) -> Heap {
+        Heap::new_with_canvas(Box::new(UnsvgCanvas::new(image)))
+    }
+    pub fn new_with_canvas(canvas: Box<dyn Canvas>) -> Heap {
         Heap {
-            variables: HashMap::new(),
-            turtle: Turtle::new_with_img(image),
+            slots: HashMap::new(),
+            names: Vec::new(),
+            variables: Vec::new(),
+            turtle: Turtle::new(canvas),
             callables: HashMap::new(),
             assignables: HashMap::new(),
+            hook: None,
+            line: 0,
+        }
+    }
+    /// Returns the environment slot of a variable name, allocating one the
+    /// first time the name is seen.
+    pub fn intern(&mut self, name: &str) -> Slot {
+        if let Some(slot) = self.slots.get(name) {
+            return *slot;
         }
+        let slot = self.names.len();
+        self.slots.insert(name.to_string(), slot);
+        self.names.push(name.to_string());
+        self.variables.push(None);
+        slot
+    }
+    pub fn slot(&self, name: &str) -> Option<Slot> {
+        self.slots.get(name).copied()
+    }
+    pub fn variable_name(&self, slot: Slot) -> &String {
+        &self.names[slot]
+    }
+    pub fn variables(&self) -> impl Iterator<Item = (&String, &Literal)> {
+        self.names
+            .iter()
+            .zip(self.variables.iter())
+            .filter_map(|(name, value)| Some((name, value.as_ref()?)))
     }
-    pub fn mut_variables(&mut self) -> &mut HashMap<String, Literal> {
-        &mut self.variables
+    pub fn variable(&self, slot: Slot) -> Option<&Literal> {
+        self.variables.get(slot)?.as_ref()
+    }
+    pub fn set_variable(&mut self, slot: Slot, value: Literal) -> Option<Literal> {
+        self.variables[slot].replace(value)
+    }
+    pub fn take_variable(&mut self, slot: Slot) -> Option<Literal> {
+        self.variables[slot].take()
     }
 
-    pub fn variable_value(&self, name: &String) -> Option<Literal> {
-        self.variables.get(name).cloned()
+    /// Binds procedure parameters and records the values they shadow. When a
+    /// tail call reuses a frame, a slot that is already recorded keeps the
+    /// first value it shadowed, which is what nested returns would restore.
+    pub fn bind_arguments(&mut self, params: &[Slot], args: Vec<Literal>, shadowed: &mut Shadowed) {
+        for param in params {
+            let value = self.take_variable(*param);
+            match shadowed.iter_mut().find(|(slot, _)| slot == param) {
+                Some((_, saved)) => {
+                    if saved.is_none() {
+                        *saved = value;
+                    }
+                }
+                None => shadowed.push((*param, value)),
+            }
+        }
+        for (param, value) in params.iter().zip(args) {
+            self.set_variable(*param, value);
+        }
+    }
+    pub fn restore_arguments(&mut self, shadowed: Shadowed) {
+        for (slot, value) in shadowed {
+            if let Some(value) = value {
+                self.set_variable(slot, value);
+            }
+        }
+    }
+
+    pub fn variable_value(&self, name: &str) -> Option<Literal> {
+        self.variable(self.slot(name)?).cloned()
     }
 
     pub fn mut_turtle(&mut self) -> &mut Turtle {
@@ -39,6 +113,26 @@ impl Heap {
         &self.turtle
     }
 
+    pub fn set_hook(&mut self, hook: Box<dyn ExecutionHook>) {
+        self.hook = Some(hook);
+    }
+    /// Source line of the statement being executed.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+    pub fn enter_statement(&mut self, statement: &Statement) {
+        self.line = statement.span().start();
+        self.with_hook(|hook, heap| hook.before_statement(statement, heap));
+    }
+    /// Lends the installed hook a read-only view of the heap. The hook is
+    /// moved out while it runs, so it never sees itself.
+    pub fn with_hook(&mut self, notify: impl FnOnce(&mut dyn ExecutionHook, &Heap)) {
+        if let Some(mut hook) = self.hook.take() {
+            notify(hook.as_mut(), self);
+            self.hook = Some(hook);
+        }
+    }
+
     pub fn get_callable(&mut self, callee: &Callee) -> &Rc<dyn Callable> {
         if !self.callables.contains_key(callee) {
             let built_in = CallableFactory::build(callee);
@@ -63,11 +157,34 @@ impl Heap {
     }
     pub fn execute_function(&mut self, callee: &Callee, args: &[Value]) -> Option<Literal> {
         let function: Rc<dyn Callable> = self.get_callable(callee).clone();
-        function.call(args, self)
+        if self.hook.is_none() || matches!(callee, Callee::Procedure(_)) {
+            return function.call(args, self);
+        }
+        // Evaluate once so the hook sees the same values the command uses.
+        let arguments: Vec<Literal> = args
+            .iter()
+            .map(|arg| {
+                arg.expect_literal_r(self)
+                    .expect("It cannot be convert to Literal Value")
+            })
+            .collect();
+        self.with_hook(|hook, heap| hook.before_call(callee, &arguments, heap));
+        let values: Vec<Value> = arguments.into_iter().map(Value::Literal).collect();
+        let result = function.call(&values, self);
+        self.with_hook(|hook, heap| hook.after_call(callee, heap));
+        result
     }
     pub fn declare_variable(&mut self, assign_type: &AssignmentType) -> Rc<dyn Assignable> {
         self.get_assignable(assign_type).clone()
     }
+    pub fn procedures(&self) -> impl Iterator<Item = (&String, &Rc<dyn Callable>)> {
+        self.callables
+            .iter()
+            .filter_map(|(callee, callable)| match callee {
+                Callee::Procedure(name) => Some((name, callable)),
+                _ => None,
+            })
+    }
     pub fn declare_procedure(&mut self, name: String, callable: Rc<dyn Callable>) {
         self.callables.insert(Callee::Procedure(name), callable);
     }
@@ -0,0 +1,26 @@
+use crate::{
+    ast::{Callee, Statement},
+    commands::Literal,
+    heap::Heap,
+};
+
+/// Observes the tree-walking interpreter. Every method has an empty default so
+/// a hook only implements the events it cares about.
+pub trait ExecutionHook {
+    /// Called before a statement runs, including statements in procedure bodies.
+    fn before_statement(&mut self, _statement: &Statement, _heap: &Heap) {}
+    /// Called once the arguments of a procedure are evaluated, before they
+    /// are bound.
+    fn enter_procedure(&mut self, _name: &str, _heap: &Heap) {}
+    fn exit_procedure(&mut self, _heap: &Heap) {}
+    /// Called when a tail call reuses the current frame. By default it is
+    /// reported as an exit followed by an enter.
+    fn tail_call(&mut self, name: &str, heap: &Heap) {
+        self.exit_procedure(heap);
+        self.enter_procedure(name, heap);
+    }
+    /// Called around a built-in command or query, with its arguments already
+    /// evaluated. Procedures report `enter_procedure` instead.
+    fn before_call(&mut self, _callee: &Callee, _arguments: &[Literal], _heap: &Heap) {}
+    fn after_call(&mut self, _callee: &Callee, _heap: &Heap) {}
+}
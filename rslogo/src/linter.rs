@@ -0,0 +1,406 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use clap::ValueEnum;
+
+use crate::{
+    ast::{Callee, ProcedureDeclaration, Statement},
+    commands::Value,
+    tokens::{ASTParser, AssignmentType, IdentifierType, Tokenizer},
+};
+
+/// A comment starting with this marker suppresses the listed codes on the
+/// line below it, e.g. `// lint: allow L003 L004`.
+const ALLOW_MARKER: &str = "lint: allow";
+
+/// The mistakes `rslogo lint` looks for. Codes are stable, so they can be
+/// passed to `--allow` or used in `// lint: allow` comments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Lint {
+    /// A variable is read before any MAKE
+    #[value(name = "L001")]
+    UndefinedVariable,
+    /// ADDASSIGN on a variable that was never made
+    #[value(name = "L002")]
+    UndefinedAddassign,
+    /// A variable is made but never read
+    #[value(name = "L003")]
+    UnusedVariable,
+    /// A procedure is never called
+    #[value(name = "L004")]
+    UnusedProcedure,
+    /// A procedure parameter has the name of a global variable
+    #[value(name = "L005")]
+    ShadowedGlobal,
+    /// Nothing in a WHILE body changes the variables of its condition
+    #[value(name = "L006")]
+    UnchangedCondition,
+}
+
+impl Lint {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Lint::UndefinedVariable => "L001",
+            Lint::UndefinedAddassign => "L002",
+            Lint::UnusedVariable => "L003",
+            Lint::UnusedProcedure => "L004",
+            Lint::ShadowedGlobal => "L005",
+            Lint::UnchangedCondition => "L006",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Warning {
+    lint: Lint,
+    line: usize,
+    message: String,
+}
+
+#[allow(dead_code)]
+impl Warning {
+    pub fn lint(&self) -> Lint {
+        self.lint
+    }
+    pub fn line(&self) -> usize {
+        self.line
+    }
+    pub fn message(&self) -> &String {
+        &self.message
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: warning[{}]: {}",
+            self.line,
+            self.lint.code(),
+            self.message
+        )
+    }
+}
+
+/// Walks the AST in program order. Scoping is dynamic, so inside a procedure
+/// body any variable made somewhere in the program counts as defined, and a
+/// top-level procedure call defines every variable the procedure makes.
+pub struct Linter {
+    procedures: HashMap<String, ProcedureDeclaration>,
+    /// Every variable made anywhere, with the line of its first MAKE.
+    made: HashMap<String, usize>,
+    globals: HashSet<String>,
+    defined: HashSet<String>,
+    reads: HashSet<String>,
+    called: HashSet<String>,
+    reported: HashSet<String>,
+    current: Option<String>,
+    warnings: Vec<Warning>,
+}
+
+impl Linter {
+    /// Panics like `ASTParser` when the source does not parse.
+    pub fn lint(source: &String) -> Vec<Warning> {
+        let mut tokenizer = Tokenizer::new(source);
+        tokenizer.scan();
+        let comments = tokenizer.move_comments();
+        let statements = ASTParser::new_from_tokens(tokenizer.move_token()).parse_statement();
+        let mut linter = Linter {
+            procedures: HashMap::new(),
+            made: HashMap::new(),
+            globals: HashSet::new(),
+            defined: HashSet::new(),
+            reads: HashSet::new(),
+            called: HashSet::new(),
+            reported: HashSet::new(),
+            current: None,
+            warnings: Vec::new(),
+        };
+        linter.collect(&statements, false);
+        linter.check(&statements);
+        linter.finish();
+
+        let allowed: Vec<(usize, String)> = comments
+            .into_iter()
+            .filter_map(|(line, comment)| {
+                let codes = comment.trim_start_matches('/').trim();
+                let codes = codes.strip_prefix(ALLOW_MARKER)?;
+                Some((line + 1, codes.to_string()))
+            })
+            .collect();
+        linter.warnings.retain(|warning| {
+            !allowed.iter().any(|(line, codes)| {
+                *line == warning.line
+                    && codes
+                        .split(|char: char| char == ',' || char.is_whitespace())
+                        .any(|code| code == warning.lint.code())
+            })
+        });
+        linter.warnings.sort_by_key(|warning| warning.line);
+        linter.warnings
+    }
+
+    /// Records procedure declarations and MAKE targets ahead of the walk.
+    fn collect(&mut self, statements: &[Statement], in_procedure: bool) {
+        for statement in statements {
+            match statement {
+                Statement::AssignmentExpression(assign) => {
+                    if *assign.operation() == AssignmentType::Make {
+                        self.made
+                            .entry(assign.left().clone())
+                            .or_insert(statement.span().start());
+                        if !in_procedure {
+                            self.globals.insert(assign.left().clone());
+                        }
+                    }
+                }
+                Statement::If(if_statement) => {
+                    self.collect(if_statement.consequent(), in_procedure)
+                }
+                Statement::While(while_statement) => {
+                    self.collect(while_statement.body(), in_procedure)
+                }
+                Statement::ProcedureDeclaration(procedure) => {
+                    self.procedures
+                        .insert(procedure.name().clone(), procedure.clone());
+                    self.collect(procedure.body(), true);
+                }
+                Statement::CallExpression(_) => {}
+            }
+        }
+    }
+
+    fn check(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            let line = statement.span().start();
+            match statement {
+                Statement::CallExpression(call) => {
+                    for argument in call.arguments() {
+                        self.read(argument, line);
+                    }
+                    if let Callee::Procedure(name) = call.callee() {
+                        self.call(name);
+                    }
+                }
+                Statement::AssignmentExpression(assign) => {
+                    self.read(assign.right(), line);
+                    let name = assign.left();
+                    if *assign.operation() == AssignmentType::Addassign
+                        && !self.defined.contains(name)
+                    {
+                        self.warn(
+                            Lint::UndefinedAddassign,
+                            line,
+                            format!("ADDASSIGN on \"{} before it is made", name),
+                        );
+                    }
+                    self.defined.insert(name.clone());
+                }
+                Statement::If(if_statement) => {
+                    self.read(if_statement.test(), line);
+                    self.check(if_statement.consequent());
+                }
+                Statement::While(while_statement) => {
+                    self.read(while_statement.test(), line);
+                    self.check_condition(while_statement.test(), while_statement.body(), line);
+                    self.check(while_statement.body());
+                }
+                Statement::ProcedureDeclaration(procedure) => self.check_procedure(procedure, line),
+            }
+        }
+    }
+
+    fn check_procedure(&mut self, procedure: &ProcedureDeclaration, line: usize) {
+        for argument in procedure.arguments() {
+            if self.globals.contains(argument) {
+                self.warn(
+                    Lint::ShadowedGlobal,
+                    line,
+                    format!(
+                        "parameter :{} of {} shadows a global variable",
+                        argument,
+                        procedure.name()
+                    ),
+                );
+            }
+        }
+        let mut defined: HashSet<String> = self.made.keys().cloned().collect();
+        defined.extend(procedure.arguments().iter().cloned());
+        let outer_defined = std::mem::replace(&mut self.defined, defined);
+        let outer = self.current.replace(procedure.name().clone());
+        self.check(procedure.body());
+        self.current = outer;
+        self.defined = outer_defined;
+    }
+
+    /// Warns when no variable of a WHILE condition is assigned in its body,
+    /// directly or by a procedure it calls. Conditions that query the turtle
+    /// can change by drawing, so they are left alone.
+    fn check_condition(&mut self, test: &Value, body: &[Statement], line: usize) {
+        let mut variables = Vec::new();
+        if variables_of(test, &mut variables) {
+            return;
+        }
+        let mut assigned = HashSet::new();
+        self.assigned(body, &mut assigned, &mut HashSet::new());
+        if !variables.iter().any(|name| assigned.contains(name)) {
+            self.warn(
+                Lint::UnchangedCondition,
+                line,
+                "WHILE condition never changes in the loop body".to_string(),
+            );
+        }
+    }
+
+    /// Collects the variables a block assigns, following procedure calls.
+    fn assigned(
+        &self,
+        statements: &[Statement],
+        assigned: &mut HashSet<String>,
+        visited: &mut HashSet<String>,
+    ) {
+        for statement in statements {
+            match statement {
+                Statement::AssignmentExpression(assign) => {
+                    assigned.insert(assign.left().clone());
+                }
+                Statement::CallExpression(call) => {
+                    if let Callee::Procedure(name) = call.callee() {
+                        if visited.insert(name.clone()) {
+                            if let Some(procedure) = self.procedures.get(name) {
+                                self.assigned(procedure.body(), assigned, visited);
+                            }
+                        }
+                    }
+                }
+                Statement::If(if_statement) => {
+                    self.assigned(if_statement.consequent(), assigned, visited)
+                }
+                Statement::While(while_statement) => {
+                    self.assigned(while_statement.body(), assigned, visited)
+                }
+                Statement::ProcedureDeclaration(_) => {}
+            }
+        }
+    }
+
+    fn call(&mut self, name: &String) {
+        if self.current.as_ref() != Some(name) {
+            self.called.insert(name.clone());
+        }
+        if self.current.is_none() {
+            if let Some(procedure) = self.procedures.get(name) {
+                let mut assigned = HashSet::new();
+                let mut visited = HashSet::from([name.clone()]);
+                self.assigned(procedure.body(), &mut assigned, &mut visited);
+                for argument in procedure.arguments() {
+                    assigned.remove(argument);
+                }
+                self.defined.extend(assigned);
+            }
+        }
+    }
+
+    fn read(&mut self, value: &Value, line: usize) {
+        match value {
+            Value::Identifier(IdentifierType::Variable(name)) => self.read_variable(name, line),
+            Value::Variable(variable) => self.read_variable(variable.name(), line),
+            Value::Statement(call) => {
+                for argument in call.arguments() {
+                    self.read(argument, line);
+                }
+                if let Callee::Procedure(name) = call.callee() {
+                    self.call(name);
+                }
+            }
+            Value::BinaryExpression(binary) => {
+                self.read(binary.left(), line);
+                self.read(binary.right(), line);
+            }
+            Value::Identifier(IdentifierType::Procedure(_))
+            | Value::Literal(_)
+            | Value::Word(_) => {}
+        }
+    }
+
+    fn read_variable(&mut self, name: &String, line: usize) {
+        self.reads.insert(name.clone());
+        if !self.defined.contains(name) && self.reported.insert(name.clone()) {
+            self.warn(
+                Lint::UndefinedVariable,
+                line,
+                format!(":{} is read before it is made", name),
+            );
+        }
+    }
+
+    /// Reports what can only be known once the whole program was walked.
+    fn finish(&mut self) {
+        let mut unused: Vec<(String, usize)> = self
+            .made
+            .iter()
+            .filter(|(name, _)| !self.reads.contains(*name))
+            .map(|(name, line)| (name.clone(), *line))
+            .collect();
+        unused.sort();
+        for (name, line) in unused {
+            self.warn(
+                Lint::UnusedVariable,
+                line,
+                format!("\"{} is made but never read", name),
+            );
+        }
+        let mut uncalled: Vec<(String, usize)> = self
+            .procedures
+            .iter()
+            .filter(|(name, _)| !self.called.contains(*name))
+            .map(|(name, procedure)| (name.clone(), procedure.span().start()))
+            .collect();
+        uncalled.sort();
+        for (name, line) in uncalled {
+            self.warn(
+                Lint::UnusedProcedure,
+                line,
+                format!("procedure {} is never called", name),
+            );
+        }
+    }
+
+    fn warn(&mut self, lint: Lint, line: usize, message: String) {
+        self.warnings.push(Warning {
+            lint,
+            line,
+            message,
+        });
+    }
+}
+
+/// Pushes the variables a value reads; returns `true` if it queries the turtle.
+fn variables_of(value: &Value, variables: &mut Vec<String>) -> bool {
+    match value {
+        Value::Identifier(IdentifierType::Variable(name)) => {
+            variables.push(name.clone());
+            false
+        }
+        Value::Variable(variable) => {
+            variables.push(variable.name().clone());
+            false
+        }
+        Value::Statement(call) => {
+            let mut queries = matches!(call.callee(), Callee::Query(_));
+            for argument in call.arguments() {
+                queries |= variables_of(argument, variables);
+            }
+            queries
+        }
+        Value::BinaryExpression(binary) => {
+            let left = variables_of(binary.left(), variables);
+            variables_of(binary.right(), variables) || left
+        }
+        Value::Identifier(IdentifierType::Procedure(_)) | Value::Literal(_) | Value::Word(_) => {
+            false
+        }
+    }
+}
@@ -0,0 +1,282 @@
+mod analysis;
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use serde_json::{json, Value as Json};
+
+use crate::{
+    panics::silence_caught_panics,
+    tokens::{CommandType, QueryType},
+    transport::Transport,
+};
+
+pub use self::analysis::{Document, Range, Symbol, SymbolKind};
+
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INCREMENTAL_SYNC: i64 = 2;
+const ERROR_SEVERITY: i64 = 1;
+const FUNCTION_COMPLETION: i64 = 3;
+const KEYWORD_COMPLETION: i64 = 14;
+
+/// A Language Server Protocol server for the documents a client has open.
+pub struct LanguageServer<R: BufRead, W: Write> {
+    transport: Transport<R, W>,
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+}
+
+impl<R: BufRead, W: Write> LanguageServer<R, W> {
+    pub fn new(input: R, output: W) -> LanguageServer<R, W> {
+        LanguageServer {
+            transport: Transport::new(input, output),
+            documents: HashMap::new(),
+            shutdown: false,
+        }
+    }
+
+    /// Serves until the client sends `exit` or closes the stream.
+    pub fn serve(&mut self) -> io::Result<()> {
+        silence_caught_panics();
+        while let Some(message) = self.transport.receive()? {
+            let method = message["method"].as_str().unwrap_or_default();
+            if method == "exit" {
+                break;
+            }
+            let params = &message["params"];
+            match message.get("id") {
+                Some(id) => {
+                    let response = match self.request(method, params) {
+                        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                        Err((code, error)) => json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            "error": { "code": code, "message": error },
+                        }),
+                    };
+                    self.transport.send(&response)?;
+                }
+                None => self.notification(method, params)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> Result<Json, (i64, String)> {
+        if self.shutdown {
+            return Err((INVALID_REQUEST, "Server is shut down".to_string()));
+        }
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": { "openClose": true, "change": INCREMENTAL_SYNC },
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": {},
+                    "renameProvider": true,
+                },
+                "serverInfo": { "name": "rslogo" },
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            }
+            "textDocument/definition" => {
+                let (uri, document, symbol) = self.symbol(params)?;
+                let locations: Vec<Json> = document
+                    .definitions(symbol)
+                    .map(|definition| location(uri, definition.range()))
+                    .collect();
+                Ok(json!(locations))
+            }
+            "textDocument/references" => {
+                let (uri, document, symbol) = self.symbol(params)?;
+                let declarations = params["context"]["includeDeclaration"]
+                    .as_bool()
+                    .unwrap_or(true);
+                let locations: Vec<Json> = document
+                    .occurrences(symbol)
+                    .filter(|other| declarations || !other.definition())
+                    .map(|other| location(uri, other.range()))
+                    .collect();
+                Ok(json!(locations))
+            }
+            "textDocument/hover" => {
+                let Ok((_, document, symbol)) = self.symbol(params) else {
+                    return Ok(Json::Null);
+                };
+                let signature = match symbol.kind() {
+                    SymbolKind::Procedure => {
+                        let Some(parameters) = document.procedure_parameters(symbol.name()) else {
+                            return Ok(Json::Null);
+                        };
+                        let mut signature = format!("TO {}", symbol.name());
+                        for parameter in parameters {
+                            signature.push_str(&format!(" :{}", parameter));
+                        }
+                        signature
+                    }
+                    SymbolKind::Variable => format!(":{}", symbol.name()),
+                };
+                Ok(json!({
+                    "contents": { "kind": "markdown", "value": format!("```logo\n{}\n```", signature) },
+                    "range": range(symbol.range()),
+                }))
+            }
+            "textDocument/completion" => {
+                let mut items: Vec<Json> = CommandType::ALL
+                    .iter()
+                    .map(|command| (command.keyword(), "command"))
+                    .chain(QueryType::ALL.iter().map(|query| (query.keyword(), "query")))
+                    .map(|(keyword, detail)| {
+                        json!({ "label": keyword, "kind": KEYWORD_COMPLETION, "detail": detail })
+                    })
+                    .collect();
+                if let Some(document) = self.documents.get(uri(params)) {
+                    let mut procedures: Vec<&String> = document.procedures().collect();
+                    procedures.sort();
+                    items.extend(procedures.into_iter().map(|procedure| {
+                        json!({ "label": procedure, "kind": FUNCTION_COMPLETION, "detail": "procedure" })
+                    }));
+                }
+                Ok(json!(items))
+            }
+            "textDocument/rename" => {
+                let new_name = params["newName"].as_str().unwrap_or_default();
+                if new_name.is_empty()
+                    || new_name.contains(char::is_whitespace)
+                    || new_name.starts_with(['"', ':', '[', ']'])
+                {
+                    return Err((
+                        INVALID_PARAMS,
+                        format!("{:?} is not a valid name", new_name),
+                    ));
+                }
+                let (uri, document, symbol) = self.symbol(params)?;
+                let edits: Vec<Json> = document
+                    .occurrences(symbol)
+                    .map(|other| json!({ "range": range(other.range()), "newText": new_name }))
+                    .collect();
+                Ok(json!({ "changes": { uri: edits } }))
+            }
+            method => Err((METHOD_NOT_FOUND, format!("Unsupported request {}", method))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) -> io::Result<()> {
+        let uri = uri(params).to_string();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents
+                    .insert(uri.clone(), Document::new(text.to_string()));
+                self.publish_diagnostics(&uri)
+            }
+            "textDocument/didChange" => {
+                let Some(document) = self.documents.get_mut(&uri) else {
+                    return Ok(());
+                };
+                for change in params["contentChanges"].as_array().into_iter().flatten() {
+                    let text = change["text"].as_str().unwrap_or_default();
+                    if change["range"].is_object() {
+                        let position = |at: &Json| {
+                            (
+                                at["line"].as_u64().unwrap_or_default() as usize,
+                                at["character"].as_u64().unwrap_or_default() as usize,
+                            )
+                        };
+                        document.edit(
+                            position(&change["range"]["start"]),
+                            position(&change["range"]["end"]),
+                            text,
+                        );
+                    } else {
+                        document.set_text(text.to_string());
+                    }
+                }
+                document.analyze();
+                self.publish_diagnostics(&uri)
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                self.transport.send(&json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/publishDiagnostics",
+                    "params": { "uri": uri, "diagnostics": [] },
+                }))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) -> io::Result<()> {
+        let Some(document) = self.documents.get(uri) else {
+            return Ok(());
+        };
+        let lines: Vec<&str> = document.text().lines().collect();
+        let diagnostics: Vec<Json> = document
+            .errors()
+            .iter()
+            .map(|error| {
+                let start = error.span().start().saturating_sub(1);
+                let end = error.span().end().saturating_sub(1);
+                let width = lines
+                    .get(end)
+                    .map(|line| line.encode_utf16().count())
+                    .unwrap_or_default();
+                json!({
+                    "range": {
+                        "start": { "line": start, "character": 0 },
+                        "end": { "line": end, "character": width },
+                    },
+                    "severity": ERROR_SEVERITY,
+                    "source": "rslogo",
+                    "message": error.message(),
+                })
+            })
+            .collect();
+        self.transport.send(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }))
+    }
+
+    /// The document and symbol under the cursor of a position request.
+    fn symbol<'a>(
+        &'a self,
+        params: &'a Json,
+    ) -> Result<(&'a str, &'a Document, &'a Symbol), (i64, String)> {
+        let uri = uri(params);
+        let document = self
+            .documents
+            .get(uri)
+            .ok_or((INVALID_PARAMS, format!("{} is not open", uri)))?;
+        let line = params["position"]["line"].as_u64().unwrap_or_default() as usize;
+        let character = params["position"]["character"].as_u64().unwrap_or_default() as usize;
+        let symbol = document
+            .symbol_at(line, character)
+            .ok_or((INVALID_PARAMS, "No procedure or variable here".to_string()))?;
+        Ok((uri, document, symbol))
+    }
+}
+
+fn uri(params: &Json) -> &str {
+    params["textDocument"]["uri"].as_str().unwrap_or_default()
+}
+
+fn range(range: Range) -> Json {
+    json!({
+        "start": { "line": range.line(), "character": range.start() },
+        "end": { "line": range.line(), "character": range.end() },
+    })
+}
+
+fn location(uri: &str, at: Range) -> Json {
+    json!({ "uri": uri, "range": range(at) })
+}
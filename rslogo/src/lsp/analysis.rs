@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use crate::tokens::{
+    ASTParser, AssignmentType, IdentifierType, KeywordType, SyntaxError, TokenType, Tokenizer,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    Procedure,
+    Variable,
+}
+
+/// A stretch of one line, 0-based, with columns in UTF-16 code units as the
+/// Language Server Protocol counts them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Range {
+    line: usize,
+    start: usize,
+    end: usize,
+}
+
+impl Range {
+    pub fn line(&self) -> usize {
+        self.line
+    }
+    pub fn start(&self) -> usize {
+        self.start
+    }
+    pub fn end(&self) -> usize {
+        self.end
+    }
+    fn contains(&self, line: usize, character: usize) -> bool {
+        self.line == line && self.start <= character && character <= self.end
+    }
+}
+
+/// A procedure or variable name, without its `"` or `:` prefix.
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    kind: SymbolKind,
+    name: String,
+    range: Range,
+    definition: bool,
+    /// For a parameter and its uses in the procedure body, the line of the
+    /// `TO` that declares it.
+    scope: Option<usize>,
+}
+
+impl Symbol {
+    pub fn kind(&self) -> SymbolKind {
+        self.kind
+    }
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+    pub fn range(&self) -> Range {
+        self.range
+    }
+    pub fn definition(&self) -> bool {
+        self.definition
+    }
+}
+
+/// One open `.lg` file. Symbols come from scanning each line with the
+/// tokenizer, so they stay available while the file does not parse.
+pub struct Document {
+    text: String,
+    symbols: Vec<Symbol>,
+    procedures: HashMap<String, Vec<String>>,
+    errors: Vec<SyntaxError>,
+}
+
+impl Document {
+    pub fn new(text: String) -> Document {
+        let mut document = Document {
+            text,
+            symbols: Vec::new(),
+            procedures: HashMap::new(),
+            errors: Vec::new(),
+        };
+        document.analyze();
+        document
+    }
+
+    /// Re-parses the text and rebuilds the symbol table.
+    pub fn analyze(&mut self) {
+        let (_, errors) = ASTParser::new(&self.text).parse_recovering();
+        self.errors = errors;
+        self.symbols.clear();
+        self.procedures.clear();
+        self.scan_symbols();
+    }
+
+    pub fn text(&self) -> &String {
+        &self.text
+    }
+    pub fn errors(&self) -> &Vec<SyntaxError> {
+        &self.errors
+    }
+    pub fn procedure_parameters(&self, name: &str) -> Option<&Vec<String>> {
+        self.procedures.get(name)
+    }
+    pub fn procedures(&self) -> impl Iterator<Item = &String> {
+        self.procedures.keys()
+    }
+
+    pub fn symbol_at(&self, line: usize, character: usize) -> Option<&Symbol> {
+        self.symbols
+            .iter()
+            .find(|symbol| symbol.range.contains(line, character))
+    }
+    /// Every use of the symbol's name in the same scope, definitions included.
+    /// Parameters are only seen between their `TO` and `END`.
+    pub fn occurrences<'a>(&'a self, symbol: &'a Symbol) -> impl Iterator<Item = &'a Symbol> {
+        self.symbols.iter().filter(|other| {
+            other.kind == symbol.kind && other.name == symbol.name && other.scope == symbol.scope
+        })
+    }
+    pub fn definitions<'a>(&'a self, symbol: &'a Symbol) -> impl Iterator<Item = &'a Symbol> {
+        self.occurrences(symbol).filter(|other| other.definition)
+    }
+
+    /// Replaces the text between two `(line, character)` positions. Call
+    /// `analyze` once all edits of a change are applied.
+    pub fn edit(&mut self, start: (usize, usize), end: (usize, usize), text: &str) {
+        let start = self.offset(start);
+        let end = self.offset(end).max(start);
+        self.text.replace_range(start..end, text);
+    }
+    pub fn set_text(&mut self, text: String) {
+        self.text = text;
+    }
+
+    fn offset(&self, (line, character): (usize, usize)) -> usize {
+        let mut offset = 0;
+        for (index, text) in self.text.split_inclusive('\n').enumerate() {
+            if index == line {
+                let mut units = 0;
+                for (byte, char) in text.char_indices() {
+                    if units >= character || char == '\n' || char == '\r' {
+                        return offset + byte;
+                    }
+                    units += char.len_utf16();
+                }
+                return offset + text.len();
+            }
+            offset += text.len();
+        }
+        self.text.len()
+    }
+
+    fn scan_symbols(&mut self) {
+        // The line of the enclosing `TO` and the parameters it declares.
+        let mut enclosing: Option<(usize, Vec<String>)> = None;
+        for (line, text) in self.text.lines().enumerate() {
+            // Label text is neither variables nor procedures.
+            if text.trim().starts_with("//") || Tokenizer::scan_label(text.trim()).is_some() {
+                continue;
+            }
+            let mut previous: Option<TokenType> = None;
+            let mut header: Option<String> = None;
+            for (start, word) in words(text) {
+                let token = Tokenizer::classify(word);
+                let prefix = usize::from(word.starts_with(['"', ':']));
+                let range = Range {
+                    line,
+                    start: start + prefix,
+                    end: start + word.encode_utf16().count(),
+                };
+                let procedure = header.clone();
+                let symbol = match (&token, &previous, &procedure) {
+                    (
+                        TokenType::Identifier(IdentifierType::Procedure(name)),
+                        Some(TokenType::Keyword(KeywordType::To)),
+                        _,
+                    ) => {
+                        header = Some(name.clone());
+                        enclosing = Some((line, Vec::new()));
+                        self.procedures.insert(name.clone(), Vec::new());
+                        Some((SymbolKind::Procedure, name.clone(), true))
+                    }
+                    (TokenType::Identifier(IdentifierType::Variable(name)), _, Some(procedure)) => {
+                        if let Some(parameters) = self.procedures.get_mut(procedure) {
+                            parameters.push(name.clone());
+                        }
+                        if let Some((_, parameters)) = enclosing.as_mut() {
+                            parameters.push(name.clone());
+                        }
+                        Some((SymbolKind::Variable, name.clone(), true))
+                    }
+                    (
+                        TokenType::Identifier(IdentifierType::Variable(name)),
+                        Some(TokenType::Assignment(AssignmentType::Make)),
+                        _,
+                    ) => Some((SymbolKind::Variable, name.clone(), true)),
+                    (TokenType::Identifier(IdentifierType::Variable(name)), _, _) => {
+                        Some((SymbolKind::Variable, name.clone(), false))
+                    }
+                    (TokenType::Identifier(IdentifierType::Procedure(name)), _, _) => {
+                        Some((SymbolKind::Procedure, name.clone(), false))
+                    }
+                    _ => None,
+                };
+                if let Some((kind, name, definition)) = symbol {
+                    let scope = match (kind, &enclosing) {
+                        (SymbolKind::Variable, Some((at, parameters)))
+                            if parameters.contains(&name) =>
+                        {
+                            Some(*at)
+                        }
+                        _ => None,
+                    };
+                    self.symbols.push(Symbol {
+                        kind,
+                        name,
+                        range,
+                        definition,
+                        scope,
+                    });
+                }
+                if matches!(token, TokenType::Keyword(KeywordType::End)) {
+                    enclosing = None;
+                }
+                previous = Some(token);
+            }
+        }
+    }
+}
+
+/// Whitespace-separated words with their starting UTF-16 column.
+fn words(text: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut column = 0;
+    let mut start: Option<(usize, usize)> = None;
+    for (byte, char) in text.char_indices() {
+        if char.is_whitespace() {
+            if let Some((begin, at)) = start.take() {
+                words.push((at, &text[begin..byte]));
+            }
+        } else if start.is_none() {
+            start = Some((byte, column));
+        }
+        column += char.len_utf16();
+    }
+    if let Some((begin, at)) = start {
+        words.push((at, &text[begin..]));
+    }
+    words
+}
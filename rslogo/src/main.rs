@@ -1,37 +1,270 @@
 mod ast;
+mod bytecode;
+mod canvas;
 mod commands;
+mod dap;
+mod debugger;
+mod display_list;
+mod font;
+mod formatter;
 mod heap;
+mod hooks;
+mod linter;
+mod lsp;
+mod panics;
 mod parsers;
 mod program;
+mod repl;
+mod resolver;
+mod svg;
 #[cfg(test)]
+#[allow(clippy::neg_multiply)]
 mod tests;
 mod tokens;
+mod trace;
 mod transpiler;
+mod transport;
 mod turtle;
 
-use clap::Parser;
-use program::Program;
-use std::fs;
+use clap::{Args, Parser, Subcommand};
+use debugger::Debugger;
+use formatter::Formatter;
+use linter::{Lint, Linter};
+use panics::{catch_quietly, silence_caught_panics};
+use program::{Engine, Program};
+use repl::Repl;
+use std::{fs, io, path::PathBuf};
+use svg::{ImageWriter, SvgWriter};
+use trace::Tracer;
+use transpiler::{SourceMapFormat, Target};
 use unsvg::Image;
 
-/// A simple program to parse four arguments using clap.
+/// Runs a Logo file, or starts one of the interactive tools.
 #[derive(Parser)]
-struct Args {
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    run: Option<RunArgs>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Read and execute statements interactively
+    Repl {
+        /// Height
+        #[arg(long, default_value_t = 500)]
+        height: u32,
+        /// Width
+        #[arg(long, default_value_t = 500)]
+        width: u32,
+    },
+    /// Run a Logo file under the step debugger
+    Debug {
+        /// Path to a file
+        file_path: PathBuf,
+        /// Save the image as .svg or .png when the program finishes
+        #[arg(long)]
+        image: Option<PathBuf>,
+        /// Height
+        #[arg(long, default_value_t = 500)]
+        height: u32,
+        /// Width
+        #[arg(long, default_value_t = 500)]
+        width: u32,
+    },
+    /// Run a Logo file, as when no subcommand is given
+    Run(RunArgs),
+    /// Serve the Debug Adapter Protocol over stdio
+    Dap,
+    /// Serve the Language Server Protocol over stdio
+    Lsp,
+    /// Rewrite Logo files with canonical indentation
+    Fmt {
+        /// Files to format in place
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Only report files that are not formatted, and fail if there are any
+        #[arg(long)]
+        check: bool,
+    },
+    /// Warn about common mistakes in Logo files
+    Lint {
+        /// Files to check
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Warning codes to suppress, e.g. --allow L003
+        #[arg(long, value_enum)]
+        allow: Vec<Lint>,
+    },
+}
+
+/// A simple program to parse four arguments using clap.
+#[derive(Args)]
+struct RunArgs {
     /// Path to a file
-    file_path: std::path::PathBuf,
+    file_path: PathBuf,
     /// Path to an svg or png image
-    image_path: std::path::PathBuf,
+    image_path: PathBuf,
 
     /// Height
     height: u32,
 
     /// Width
     width: u32,
+
+    /// Execution engine used to run the program
+    #[arg(long, value_enum, default_value_t = Engine::Interpreter)]
+    engine: Engine,
+
+    /// Write a JSON Lines record for every executed command
+    #[arg(long)]
+    trace: Option<PathBuf>,
+
+    /// Read the file as a JSON AST instead of Logo source
+    #[arg(long)]
+    ast: bool,
+
+    /// Language the program is transpiled to and printed as
+    #[arg(long, value_enum, default_value_t = Target::Python)]
+    emit: Target,
+
+    /// Write a map from the transpiled lines back to source lines
+    #[arg(long)]
+    source_map: Option<PathBuf>,
+
+    /// Format of the --source-map file
+    #[arg(long, value_enum, default_value_t = SourceMapFormat::Jsonl)]
+    source_map_format: SourceMapFormat,
+
+    /// Write everything the program drew as JSON
+    #[arg(long)]
+    export: Option<PathBuf>,
+
+    /// Writer used to save the image
+    #[arg(long, value_enum, default_value_t = ImageWriter::Auto)]
+    image_writer: ImageWriter,
+
+    /// Decimals kept in coordinates by the native SVG writer
+    #[arg(long, default_value_t = 2)]
+    svg_precision: usize,
 }
 
 fn main() -> Result<(), ()> {
-    let args: Args = Args::parse();
+    let cli: Cli = Cli::parse();
+    match (cli.command, cli.run) {
+        (Some(Command::Repl { height, width }), _) => {
+            Repl::new(Image::new(width, height)).start().map_err(|err| {
+                eprintln!("{}", err);
+            })
+        }
+        (
+            Some(Command::Debug {
+                file_path,
+                image,
+                height,
+                width,
+            }),
+            _,
+        ) => debug(file_path, image, height, width),
+        (Some(Command::Dap), _) => dap::serve(io::stdin().lock(), io::stdout()).map_err(|err| {
+            eprintln!("{}", err);
+        }),
+        (Some(Command::Lsp), _) => lsp::LanguageServer::new(io::stdin().lock(), io::stdout())
+            .serve()
+            .map_err(|err| {
+                eprintln!("{}", err);
+            }),
+        (Some(Command::Fmt { files, check }), _) => fmt(files, check),
+        (Some(Command::Lint { files, allow }), _) => lint(files, allow),
+        (Some(Command::Run(args)), _) | (None, Some(args)) => run(args),
+        (None, None) => unreachable!("clap requires either a subcommand or the run arguments"),
+    }
+}
 
+fn debug(
+    file_path: PathBuf,
+    image_path: Option<PathBuf>,
+    height: u32,
+    width: u32,
+) -> Result<(), ()> {
+    let contents = fs::read_to_string(&file_path).unwrap_or_else(|err| {
+        println!("{:?}", err);
+        std::process::exit(1);
+    });
+    let mut program = Program::parse_logo(&contents, Image::new(width, height));
+    program.set_hook(Box::new(Debugger::new(
+        &contents,
+        io::stdin().lock(),
+        io::stdout(),
+    )));
+    program.run();
+    println!("Program finished");
+    match image_path {
+        Some(image_path) => program.save_image(&image_path).map_err(|err| {
+            eprintln!("{err}");
+        }),
+        None => Ok(()),
+    }
+}
+
+fn fmt(files: Vec<PathBuf>, check: bool) -> Result<(), ()> {
+    silence_caught_panics();
+    let mut unformatted = false;
+    for file_path in files {
+        let contents = fs::read_to_string(&file_path).map_err(|err| {
+            eprintln!("{}: {}", file_path.display(), err);
+        })?;
+        let formatted = catch_quietly(|| Formatter::format(&contents)).map_err(|err| {
+            eprintln!("{}: {}", file_path.display(), err);
+        })?;
+        if formatted == contents {
+            continue;
+        }
+        if check {
+            println!("{} is not formatted", file_path.display());
+            unformatted = true;
+        } else {
+            fs::write(&file_path, formatted).map_err(|err| {
+                eprintln!("{}: {}", file_path.display(), err);
+            })?;
+        }
+    }
+    if unformatted {
+        Err(())
+    } else {
+        Ok(())
+    }
+}
+
+fn lint(files: Vec<PathBuf>, allow: Vec<Lint>) -> Result<(), ()> {
+    silence_caught_panics();
+    let mut warned = false;
+    for file_path in files {
+        let contents = fs::read_to_string(&file_path).map_err(|err| {
+            eprintln!("{}: {}", file_path.display(), err);
+        })?;
+        let warnings = catch_quietly(|| Linter::lint(&contents)).map_err(|err| {
+            eprintln!("{}: {}", file_path.display(), err);
+        })?;
+        for warning in warnings {
+            if allow.contains(&warning.lint()) {
+                continue;
+            }
+            println!("{}:{}", file_path.display(), warning);
+            warned = true;
+        }
+    }
+    if warned {
+        Err(())
+    } else {
+        Ok(())
+    }
+}
+
+fn run(args: RunArgs) -> Result<(), ()> {
     // Access the parsed arguments
     let file_path = args.file_path;
     // let image_path = std::path::PathBuf::from("/home/shilong/cs6991/assign/rslogo/output.svg");
@@ -41,36 +274,54 @@ fn main() -> Result<(), ()> {
     let height = args.height;
     let width = args.width;
 
-    let contents = fs::read_to_string(file_path).unwrap_or_else(|err| {
+    let contents = fs::read_to_string(&file_path).unwrap_or_else(|err| {
         println!("{:?}", err);
         std::process::exit(1);
     });
     let image = Image::new(width, height);
-    let mut program = Program::parse_logo(&contents, image);
-    program.run();
-    println!("{}", program.to_python());
-    let image = program.image();
-
-    match image_path.extension().and_then(|s| s.to_str()) {
-        Some("svg") => {
-            let res = image.save_svg(&image_path);
-            if let Err(e) = res {
-                eprintln!("Error saving svg: {e}");
-                return Err(());
-            }
-        }
-        Some("png") => {
-            let res = image.save_png(&image_path);
-            if let Err(e) = res {
-                eprintln!("Error saving png: {e}");
-                return Err(());
-            }
-        }
-        _ => {
-            eprintln!("File extension not supported");
+    let mut program = if args.ast {
+        Program::parse_ast(&contents, image).map_err(|err| {
+            eprintln!("{err}");
+        })?
+    } else {
+        Program::parse_logo(&contents, image)
+    };
+    program.set_engine(args.engine);
+    if let Some(trace_path) = args.trace {
+        if args.engine != Engine::Interpreter {
+            eprintln!("--trace is only supported by the interpreter engine");
             return Err(());
         }
+        let file = fs::File::create(&trace_path).map_err(|err| {
+            eprintln!("Error creating trace file: {err}");
+        })?;
+        program.set_hook(Box::new(Tracer::new(io::BufWriter::new(file))));
+    }
+    program.run();
+    let (source, map) = program.emit_mapped(args.emit);
+    println!("{}", source);
+    if let Some(map_path) = args.source_map {
+        let map = map.write(args.source_map_format, &file_path.to_string_lossy());
+        fs::write(&map_path, map).map_err(|err| {
+            eprintln!("Error writing source map: {err}");
+        })?;
+    }
+
+    if let Some(export_path) = args.export {
+        fs::write(&export_path, program.display_list().to_json()).map_err(|err| {
+            eprintln!("Error writing display list: {err}");
+        })?;
+    }
+
+    if args.image_writer.resolve(program.display_list()) == ImageWriter::Native {
+        return SvgWriter::new(args.svg_precision)
+            .save(program.display_list(), &image_path)
+            .map_err(|err| {
+                eprintln!("{err}");
+            });
     }
 
-    Ok(())
+    program.save_image(&image_path).map_err(|err| {
+        eprintln!("{err}");
+    })
 }
@@ -0,0 +1,43 @@
+use std::{
+    any::Any,
+    cell::Cell,
+    panic::{catch_unwind, set_hook, take_hook, AssertUnwindSafe},
+};
+
+thread_local! {
+    static QUIET: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether the current thread is inside `catch_quietly`. Panic hooks check
+/// this so an expected, recovered panic neither prints nor exits.
+pub fn is_quiet() -> bool {
+    QUIET.with(Cell::get)
+}
+
+/// Runs `f`, turning a panic into its message.
+pub fn catch_quietly<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    let outer = QUIET.with(|quiet| quiet.replace(true));
+    let result = catch_unwind(AssertUnwindSafe(f));
+    QUIET.with(|quiet| quiet.set(outer));
+    result.map_err(|payload| panic_message(payload.as_ref()))
+}
+
+/// Keeps the current panic hook for unexpected panics only.
+pub fn silence_caught_panics() {
+    let previous = take_hook();
+    set_hook(Box::new(move |panic_info| {
+        if !is_quiet() {
+            previous(panic_info);
+        }
+    }));
+}
+
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown error".to_string()
+    }
+}
@@ -18,6 +18,7 @@ impl TokenType {
         match self {
             TokenType::Identifier(identifier) => Some(Value::Identifier(identifier.clone())),
             TokenType::Value(value) => Some(Value::Literal(value.clone())),
+            TokenType::Word(word) => Some(Value::Word(word.clone())),
             TokenType::Query(query) => {
                 let query_token: Vec<TokenType> = vec![TokenType::Query(query.clone())];
                 Some(Value::Statement(Box::new(
@@ -38,7 +39,7 @@ impl ParserFactory {
     pub fn create(tokens: &Vec<TokenType>) -> Box<dyn StatementParser> {
         // println!("ParserFactory: Parsing Token '{:?}'", tokens);
         let first = tokens
-            .get(0)
+            .first()
             .expect("ParserFactory::create accept only non-empty tokens");
         match first {
             TokenType::Assignment(_) => Box::new(variable::VariableAssignParser {}),
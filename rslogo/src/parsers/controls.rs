@@ -1,7 +1,7 @@
 use crate::{
     ast::{CallExpression, Callee, Statement},
     commands::Value,
-    tokens::{CommandType, StatementParser, TokenType},
+    tokens::{CommandType, IdentifierType, StatementParser, TokenType, ValueType},
 };
 
 pub struct ControlsParser {}
@@ -17,21 +17,58 @@ impl ControlsParser {
         let command = first
             .expect_command()
             .expect("COntrols Parser accpect only command");
+        if command == CommandType::Label {
+            return match itr.as_slice() {
+                [TokenType::Word(word)] => Statement::CallExpression(CallExpression::new(
+                    Callee::Command(command),
+                    vec![Value::Word(word.clone())],
+                )),
+                _ => panic!("Error: LABEL takes a \"word or a [list of words]"),
+            };
+        }
         let mut args = Vec::new();
+        // Colours given as `[r g b]` or `"#rrggbb` become three arguments.
+        let mut rgb_start = None;
         // println!("{}", serde_json::to_string_pretty(buf).unwrap());
         while itr.len() > 0 {
+            if itr.as_slice()[0].is_left_bracket() {
+                itr.next();
+                rgb_start = Some(args.len());
+                while !itr
+                    .as_slice()
+                    .first()
+                    .expect("Missing ] after the colour")
+                    .is_right_bracket()
+                {
+                    args.push(Value::parse_one(&mut itr));
+                }
+                itr.next();
+                if args.len() - rgb_start.unwrap() != 3 {
+                    panic!("Error: a colour list needs three channels, [ red green blue ]")
+                }
+                continue;
+            }
             let arg_value = Value::parse_one(&mut itr);
             // arg.to_value().expect("It is not epxected data type");
-            args.push(arg_value);
+            match arg_value {
+                Value::Identifier(IdentifierType::Variable(word)) if word.starts_with('#') => {
+                    rgb_start = Some(args.len());
+                    args.extend(ControlsParser::hex_color(&word));
+                }
+                arg_value => args.push(arg_value),
+            }
         }
-        if ControlsParser::args_check(&command, &args) {
+        if ControlsParser::args_check(&command, &args, rgb_start) {
             let callee = Callee::Command(command);
             Statement::CallExpression(CallExpression::new(callee, args))
         } else {
             panic!("Error number of args")
         }
     }
-    fn args_check(command: &CommandType, args: &Vec<Value>) -> bool {
+    fn args_check(command: &CommandType, args: &[Value], rgb_start: Option<usize>) -> bool {
+        if rgb_start.is_some() {
+            return command.rgb_start(args.len()) == rgb_start;
+        }
         // match (command, args.len()) {
         //     (CommandType::Penup, 0) => true,
         //     (CommandType::Pendown, 0) => true,
@@ -59,6 +96,26 @@ impl ControlsParser {
                 | (CommandType::Setheading, 1)
                 | (CommandType::Setx, 1)
                 | (CommandType::Sety, 1)
+                | (CommandType::Setpensize, 1)
+                | (CommandType::Arc, 2)
+                | (CommandType::Circle, 1)
+                | (CommandType::Dot, 0)
+                | (CommandType::Beginfill, 0)
+                | (CommandType::Endfill, 0)
+                | (CommandType::Setfillcolor, 1)
+                | (CommandType::Setlabelheight, 1)
         )
     }
+    fn hex_color(word: &str) -> Vec<Value> {
+        let digits = word.strip_prefix('#').unwrap();
+        if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            panic!("Error: {} is not a colour, expected #rrggbb", word)
+        }
+        (0..3)
+            .map(|index| {
+                let channel = i32::from_str_radix(&digits[index * 2..index * 2 + 2], 16).unwrap();
+                Value::Literal(ValueType::Int(channel))
+            })
+            .collect()
+    }
 }
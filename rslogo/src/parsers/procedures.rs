@@ -72,7 +72,7 @@ fn parse_call(buf: &[TokenType]) -> Statement {
 
 fn syntax_check(buf: &[TokenType]) -> ProcedureParserState {
     let first = buf
-        .get(0)
+        .first()
         .expect("Procedure Parser accept only one token")
         .expect_keywords();
     let last = buf
@@ -16,7 +16,9 @@ impl QueriesParser {
         if tokens.len() != 1 {
             panic!("Queries Parser accept only one token");
         }
-        let query = tokens.get(0).expect("Queries Parser accept only one token");
+        let query = tokens
+            .first()
+            .expect("Queries Parser accept only one token");
         let expression: CallExpression = match query {
             TokenType::Query(query) => {
                 CallExpression::new(Callee::Query(query.clone()), Vec::new())
@@ -1,51 +1,140 @@
-use crate::transpiler::Translater;
 use crate::{
     ast::{Executable, Statement},
+    bytecode::{Bytecode, Compiler, Vm},
+    canvas::{Canvas, UnsvgCanvas},
+    display_list::DisplayList,
     heap::Heap,
+    hooks::ExecutionHook,
+    panics::is_quiet,
+    resolver::Resolvable,
     tokens::ASTParser,
+    transpiler::{BackendFactory, SourceMap, Target},
 };
-use std::panic::set_hook;
+use clap::ValueEnum;
+use std::{panic::set_hook, path::Path};
 use unsvg::Image;
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Engine {
+    /// Walk the AST statement by statement
+    #[default]
+    Interpreter,
+    /// Compile to bytecode and run it on the stack VM
+    Bytecode,
+}
+
 pub struct Program {
     statements: Vec<Statement>,
     heap: Heap,
+    engine: Engine,
 }
 #[allow(dead_code)]
 impl Program {
     pub fn new(statements: Vec<Statement>, heap: Heap) -> Program {
-        Program { heap, statements }
+        Program {
+            heap,
+            statements,
+            engine: Engine::default(),
+        }
     }
     pub fn parse_logo(logo: &String, image: Image) -> Program {
-        set_hook(Box::new(|panic_info| {
-            println!("Panic: {:?}", panic_info.to_string());
-            std::process::exit(1);
-        }));
+        exit_on_panic();
+        Program::parse(logo, image)
+    }
+    /// Builds a program from the JSON the AST serializes to, as written by
+    /// tools that produce statements without going through Logo source.
+    pub fn parse_ast(ast: &str, image: Image) -> Result<Program, String> {
+        let statements: Vec<Statement> =
+            serde_json::from_str(ast).map_err(|err| format!("Invalid AST: {err}"))?;
+        exit_on_panic();
+        Ok(Program::from_statements(statements, Heap::new(image)))
+    }
+    /// Like `parse_logo`, but leaves syntax errors to the caller's panic handling.
+    pub fn parse(logo: &String, image: Image) -> Program {
+        Program::parse_with_canvas(logo, Box::new(UnsvgCanvas::new(image)))
+    }
+    /// Like `parse`, but draws on any canvas, e.g. a `NullCanvas` for
+    /// headless runs.
+    pub fn parse_with_canvas(logo: &String, canvas: Box<dyn Canvas>) -> Program {
         let mut parser = ASTParser::new(logo);
-        let statements = parser.parse_statement();
-        Program::new(statements, Heap::new(image))
+        Program::from_statements(parser.parse_statement(), Heap::new_with_canvas(canvas))
     }
-    pub fn run(&mut self) {
-        for statement in &self.statements {
+    fn from_statements(mut statements: Vec<Statement>, mut heap: Heap) -> Program {
+        statements.resolve(&mut heap);
+        Program::new(statements, heap)
+    }
+    /// Parses more source and runs it against the existing heap, keeping the
+    /// statements so the program can still be transpiled as a whole.
+    pub fn eval(&mut self, logo: &String) {
+        let mut parser = ASTParser::new(logo);
+        let mut statements = parser.parse_statement();
+        statements.resolve(&mut self.heap);
+        for statement in &statements {
             statement.execute(&mut self.heap);
         }
+        self.statements.extend(statements);
+    }
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+    }
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
+    /// Hooks only observe the interpreter; the bytecode engine ignores them.
+    pub fn set_hook(&mut self, hook: Box<dyn ExecutionHook>) {
+        self.heap.set_hook(hook);
+    }
+    pub fn run(&mut self) {
+        match self.engine {
+            Engine::Interpreter => {
+                for statement in &self.statements {
+                    statement.execute(&mut self.heap);
+                }
+            }
+            Engine::Bytecode => self.run_bytecode(),
+        }
+    }
+    pub fn compile(&mut self) -> Bytecode {
+        Compiler::compile(&self.statements, &mut self.heap)
+    }
+    fn run_bytecode(&mut self) {
+        let bytecode = self.compile();
+        Vm::new(&bytecode).run(&mut self.heap);
     }
     pub fn image(&self) -> &Image {
         self.heap.turtle().image()
     }
+    /// Everything the run drew, whatever canvas it drew on.
+    pub fn display_list(&self) -> &DisplayList {
+        self.heap.turtle().display_list()
+    }
+    pub fn save_image(&self, image_path: &Path) -> Result<(), String> {
+        self.heap.turtle().canvas().save(image_path)
+    }
     pub fn statements(&self) -> &Vec<Statement> {
         &self.statements
     }
     pub fn heap(&self) -> &Heap {
         &self.heap
     }
-    // /*
-    pub fn to_python(&self) -> String {
-        let mut buf = String::new();
-        for statement in &self.statements {
-            buf.push_str(&statement.to_python(0));
-        }
-        buf
+    pub fn emit(&self, target: Target) -> String {
+        self.emit_mapped(target).0
+    }
+    /// The generated source, with the statement each of its lines came from.
+    pub fn emit_mapped(&self, target: Target) -> (String, SourceMap) {
+        let (width, height) = self.heap.turtle().canvas().dimensions();
+        let mut map = SourceMap::new();
+        let source = BackendFactory::build(target).emit(&self.statements, width, height, &mut map);
+        (source, map)
     }
-    // */
+}
+
+fn exit_on_panic() {
+    set_hook(Box::new(|panic_info| {
+        if is_quiet() {
+            return;
+        }
+        println!("Panic: {:?}", panic_info.to_string());
+        std::process::exit(1);
+    }));
 }
@@ -0,0 +1,173 @@
+use std::{
+    io::{self, BufRead, Write},
+    panic::{catch_unwind, set_hook, AssertUnwindSafe},
+    path::Path,
+};
+
+use unsvg::Image;
+
+use crate::{
+    heap::Heap,
+    panics::panic_message,
+    program::Program,
+    tokens::{KeywordType, TokenType, Tokenizer},
+};
+
+const HELP: &str = "\
+.help              show this message
+.save <file>       save the image as .svg or .png
+.procedures        list defined procedures
+.variables         list variables and their values
+.quit              leave the REPL";
+
+/// Reads Logo line by line and runs every complete statement against one
+/// persistent heap.
+pub struct Repl {
+    program: Program,
+    buffer: String,
+}
+
+impl Repl {
+    pub fn new(image: Image) -> Repl {
+        Repl {
+            program: Program::new(Vec::new(), Heap::new(image)),
+            buffer: String::new(),
+        }
+    }
+
+    /// Runs the REPL on stdin/stdout. Errors are reported and the session
+    /// carries on instead of exiting the process.
+    pub fn start(&mut self) -> io::Result<()> {
+        set_hook(Box::new(|panic_info| {
+            eprintln!("Error: {}", panic_message(panic_info.payload()));
+        }));
+        let stdin = io::stdin();
+        let mut stdout = io::stdout();
+        self.run(stdin.lock(), &mut stdout)
+    }
+
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, output: &mut W) -> io::Result<()> {
+        self.prompt(output)?;
+        for line in input.lines() {
+            let line = line?;
+            if self.buffer.is_empty() && line.trim().starts_with('.') {
+                if !self.command(line.trim(), output)? {
+                    return Ok(());
+                }
+            } else {
+                self.buffer.push_str(&line);
+                self.buffer.push('\n');
+                if is_complete(&self.buffer) {
+                    let source = std::mem::take(&mut self.buffer);
+                    self.execute(&source, output)?;
+                }
+            }
+            self.prompt(output)?;
+        }
+        Ok(())
+    }
+
+    fn prompt<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            write!(output, "> ")?;
+        } else {
+            write!(output, "... ")?;
+        }
+        output.flush()
+    }
+
+    fn execute<W: Write>(&mut self, source: &String, output: &mut W) -> io::Result<()> {
+        let program = &mut self.program;
+        let result = catch_unwind(AssertUnwindSafe(|| program.eval(source)));
+        if result.is_ok() {
+            let turtle = self.program.heap().turtle();
+            writeln!(
+                output,
+                "XCOR {} YCOR {} HEADING {}",
+                turtle.x(),
+                turtle.y(),
+                turtle.direction()
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Handles a `.command`; returns `false` when the session should end.
+    fn command<W: Write>(&mut self, line: &str, output: &mut W) -> io::Result<bool> {
+        let mut words = line.split_ascii_whitespace();
+        match words.next().unwrap_or_default() {
+            ".help" => writeln!(output, "{}", HELP)?,
+            ".quit" | ".exit" => return Ok(false),
+            ".save" => match words.next() {
+                Some(path) => match self.program.save_image(Path::new(path)) {
+                    Ok(()) => writeln!(output, "Saved {}", path)?,
+                    Err(err) => writeln!(output, "Error: {}", err)?,
+                },
+                None => writeln!(output, "Error: .save expects a file name")?,
+            },
+            ".procedures" => {
+                let heap = self.program.heap();
+                let mut procedures: Vec<String> = heap
+                    .procedures()
+                    .map(|(name, callable)| {
+                        let mut signature = name.clone();
+                        if let Some(procedure) = callable.as_procedure() {
+                            for slot in procedure.arguments() {
+                                signature.push_str(&format!(" :{}", heap.variable_name(*slot)));
+                            }
+                        }
+                        signature
+                    })
+                    .collect();
+                procedures.sort();
+                for procedure in procedures {
+                    writeln!(output, "{}", procedure)?;
+                }
+            }
+            ".variables" => {
+                let mut variables: Vec<_> = self.program.heap().variables().collect();
+                variables.sort_by(|left, right| left.0.cmp(right.0));
+                for (name, value) in variables {
+                    writeln!(output, "{} = {}", name, value)?;
+                }
+            }
+            command => writeln!(output, "Error: unknown command {}, try .help", command)?,
+        }
+        Ok(true)
+    }
+}
+
+/// A statement is complete once every `[` has its `]` and every `TO` its `END`.
+/// An IF or WHILE line that does not end with `[` is still waiting for one.
+fn is_complete(source: &String) -> bool {
+    let mut tokenizer = Tokenizer::new(source);
+    tokenizer.scan();
+    let (mut brackets, mut procedures) = (0, 0);
+    let (mut block_line, mut awaiting_block) = (false, false);
+    let mut previous: Option<TokenType> = None;
+    for token in tokenizer.move_token() {
+        match token {
+            TokenType::Bracketleft => {
+                brackets += 1;
+                awaiting_block = false;
+            }
+            TokenType::Bracketright => brackets -= 1,
+            TokenType::Keyword(KeywordType::To) => procedures += 1,
+            TokenType::Keyword(KeywordType::End) => procedures -= 1,
+            TokenType::Keyword(KeywordType::If | KeywordType::While)
+                if matches!(previous, Some(TokenType::Line(_))) =>
+            {
+                block_line = true;
+            }
+            TokenType::Space => {
+                if block_line && !matches!(previous, Some(TokenType::Bracketleft)) {
+                    awaiting_block = true;
+                }
+                block_line = false;
+            }
+            _ => {}
+        }
+        previous = Some(token);
+    }
+    brackets <= 0 && procedures <= 0 && !awaiting_block
+}
@@ -0,0 +1,103 @@
+use std::mem::take;
+
+use crate::{
+    ast::{
+        AssignmentExpression, BinaryExpression, CallExpression, IfStatement, ProcedureDeclaration,
+        Statement, WhileStatement,
+    },
+    commands::{Value, Variable},
+    heap::Heap,
+    tokens::IdentifierType,
+};
+
+/// Assigns every variable in the AST a slot in the heap's environment so
+/// reads and assignments no longer go through the name table.
+pub trait Resolvable {
+    fn resolve(&mut self, heap: &mut Heap);
+}
+
+impl Resolvable for Vec<Statement> {
+    fn resolve(&mut self, heap: &mut Heap) {
+        for statement in self.iter_mut() {
+            statement.resolve(heap);
+        }
+    }
+}
+
+impl Resolvable for Statement {
+    fn resolve(&mut self, heap: &mut Heap) {
+        match self {
+            Statement::CallExpression(call) => call.resolve(heap),
+            Statement::AssignmentExpression(assign) => assign.resolve(heap),
+            Statement::If(if_statement) => if_statement.resolve(heap),
+            Statement::While(while_statement) => while_statement.resolve(heap),
+            Statement::ProcedureDeclaration(procedure_declaration) => {
+                procedure_declaration.resolve(heap)
+            }
+        }
+    }
+}
+
+impl Resolvable for CallExpression {
+    fn resolve(&mut self, heap: &mut Heap) {
+        for argument in self.arguments_mut() {
+            argument.resolve(heap);
+        }
+    }
+}
+
+impl Resolvable for AssignmentExpression {
+    fn resolve(&mut self, heap: &mut Heap) {
+        self.right_mut().resolve(heap);
+        let slot = heap.intern(self.left());
+        self.set_slot(slot);
+    }
+}
+
+impl Resolvable for IfStatement {
+    fn resolve(&mut self, heap: &mut Heap) {
+        self.test_mut().resolve(heap);
+        self.consequent_mut().resolve(heap);
+    }
+}
+
+impl Resolvable for WhileStatement {
+    fn resolve(&mut self, heap: &mut Heap) {
+        self.test_mut().resolve(heap);
+        self.body_mut().resolve(heap);
+    }
+}
+
+impl Resolvable for ProcedureDeclaration {
+    fn resolve(&mut self, heap: &mut Heap) {
+        for argument in self.arguments() {
+            heap.intern(argument);
+        }
+        self.body_mut().resolve(heap);
+    }
+}
+
+impl Resolvable for BinaryExpression {
+    fn resolve(&mut self, heap: &mut Heap) {
+        self.left_mut().resolve(heap);
+        self.right_mut().resolve(heap);
+    }
+}
+
+impl Resolvable for Value {
+    fn resolve(&mut self, heap: &mut Heap) {
+        match self {
+            Value::Identifier(IdentifierType::Variable(name)) => {
+                let name = take(name);
+                let slot = heap.intern(&name);
+                *self = Value::Variable(Variable::new(name, slot));
+            }
+            Value::Statement(call) => call.resolve(heap),
+            Value::BinaryExpression(binary) => binary.resolve(heap),
+            Value::Identifier(IdentifierType::Procedure(_))
+            | Value::Literal(_)
+            | Value::Variable(_)
+            | Value::Word(_) => {}
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use clap::ValueEnum;
+use resvg::{
+    tiny_skia::Pixmap,
+    usvg::{Options, Tree, TreeParsing},
+};
+use std::{fmt::Write, path::Path};
+
+use crate::{
+    display_list::{Arc, DisplayList, Dot, Fill, Label, Primitive, Rgb},
+    turtle::{DEFAULT_LABEL_HEIGHT, DEFAULT_PEN_SIZE},
+};
+
+/// Which writer produces `.svg` and `.png` images.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ImageWriter {
+    /// unsvg, unless the drawing uses something only the native writer draws
+    #[default]
+    Auto,
+    /// One `<line>` per move, as unsvg draws it
+    Unsvg,
+    /// Merged paths grouped by colour, written from the display list
+    Native,
+}
+
+impl ImageWriter {
+    /// Settles `Auto` for a finished drawing. unsvg strokes every line with
+    /// the default pen size and can only approximate curves.
+    pub fn resolve(self, display_list: &DisplayList) -> ImageWriter {
+        let unsvg_can_draw = |primitive: &Primitive| match primitive {
+            Primitive::Segment(segment) => segment.width() == DEFAULT_PEN_SIZE,
+            _ => false,
+        };
+        match self {
+            ImageWriter::Auto if !display_list.primitives().iter().all(unsvg_can_draw) => {
+                ImageWriter::Native
+            }
+            ImageWriter::Auto => ImageWriter::Unsvg,
+            writer => writer,
+        }
+    }
+}
+
+/// Writes a display list as compact SVG. Segments that continue one another
+/// with the same colour and width become a single `<path>`, and consecutive
+/// shapes that share a stroke are grouped, so the drawing order is kept.
+/// Arcs are SVG arcs or circles, dots are filled circles, fills are
+/// polygons and labels are `<text>`.
+pub struct SvgWriter {
+    precision: usize,
+}
+
+#[allow(dead_code)]
+impl SvgWriter {
+    /// `precision` is the number of decimals kept in coordinates. Positions
+    /// are multiples of 1/256, so 8 keeps them exact.
+    pub fn new(precision: usize) -> SvgWriter {
+        SvgWriter { precision }
+    }
+    pub fn precision(&self) -> usize {
+        self.precision
+    }
+    pub fn write(&self, display_list: &DisplayList) -> String {
+        self.document(display_list, false)
+    }
+    /// Writes SVG, or renders it to PNG, as the extension says. Labels in a
+    /// PNG are drawn in the stroke font, since no fonts are loaded.
+    pub fn save(&self, display_list: &DisplayList, path: &Path) -> Result<(), String> {
+        match path.extension().and_then(|s| s.to_str()) {
+            Some("svg") => std::fs::write(path, self.write(display_list))
+                .map_err(|e| format!("Error saving svg: {e}")),
+            Some("png") => {
+                let tree = Tree::from_str(&self.document(display_list, true), &Options::default())
+                    .map_err(|e| format!("Error saving png: {e}"))?;
+                let size = tree.size.to_int_size();
+                let mut pixmap = Pixmap::new(size.width(), size.height())
+                    .ok_or("Error saving png: the image is empty")?;
+                resvg::Tree::from_usvg(&tree).render(Default::default(), &mut pixmap.as_mut());
+                pixmap
+                    .save_png(path)
+                    .map_err(|e| format!("Error saving png: {e}"))
+            }
+            _ => Err("File extension not supported".to_string()),
+        }
+    }
+    fn document(&self, display_list: &DisplayList, outline_labels: bool) -> String {
+        let (width, height) = display_list.dimensions();
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+        )
+        .unwrap();
+        writeln!(svg, r#"<rect width="{width}" height="{height}"/>"#).unwrap();
+        let shapes = shapes(display_list.primitives(), outline_labels);
+        let mut index = 0;
+        while index < shapes.len() {
+            let Some(stroke) = shapes[index].stroke() else {
+                writeln!(svg, "{}", self.element(&shapes[index])).unwrap();
+                index += 1;
+                continue;
+            };
+            writeln!(
+                svg,
+                r#"<g fill="none" stroke="{}" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round">"#,
+                stroke.0.hex(),
+                self.number(stroke.1)
+            )
+            .unwrap();
+            while index < shapes.len() && shapes[index].stroke() == Some(stroke) {
+                writeln!(svg, "{}", self.element(&shapes[index])).unwrap();
+                index += 1;
+            }
+            svg.push_str("</g>\n");
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+    fn element(&self, shape: &Shape) -> String {
+        match shape {
+            Shape::Run(run) => format!(r#"<path d="{}"/>"#, self.path(&run.points)),
+            Shape::Arc(arc) if arc.is_circle() => format!(
+                r#"<circle cx="{}" cy="{}" r="{}"/>"#,
+                self.number(arc.center().0),
+                self.number(arc.center().1),
+                self.number(arc.radius().abs())
+            ),
+            Shape::Arc(arc) => {
+                let (from, to) = (arc.point(0.0), arc.point(arc.sweep()));
+                format!(
+                    r#"<path d="M{} {}A{} {} 0 {} {} {} {}"/>"#,
+                    self.number(from.0),
+                    self.number(from.1),
+                    self.number(arc.radius().abs()),
+                    self.number(arc.radius().abs()),
+                    u8::from(arc.sweep().abs() > 180.0),
+                    u8::from(arc.sweep() > 0.0),
+                    self.number(to.0),
+                    self.number(to.1)
+                )
+            }
+            Shape::Dot(dot) => format!(
+                r#"<circle cx="{}" cy="{}" r="{}" fill="{}"/>"#,
+                self.number(dot.center().0),
+                self.number(dot.center().1),
+                self.number(dot.diameter() / 2.0),
+                dot.color().hex()
+            ),
+            Shape::Fill(fill) => {
+                let points: Vec<String> = fill
+                    .points()
+                    .iter()
+                    .map(|(x, y)| format!("{},{}", self.number(*x), self.number(*y)))
+                    .collect();
+                format!(
+                    r#"<polygon points="{}" fill="{}"/>"#,
+                    points.join(" "),
+                    fill.color().hex()
+                )
+            }
+            Shape::Label(label) => {
+                let (x, y) = (
+                    self.number(label.position().0),
+                    self.number(label.position().1),
+                );
+                let rotate = match label.heading().rem_euclid(360.0) {
+                    0.0 => String::new(),
+                    heading => format!(r#" transform="rotate({} {x} {y})""#, self.number(heading)),
+                };
+                format!(
+                    r#"<text x="{x}" y="{y}" font-family="sans-serif" font-size="{}" fill="{}"{rotate}>{}</text>"#,
+                    self.number(label.height()),
+                    label.color().hex(),
+                    escape(label.text())
+                )
+            }
+        }
+    }
+    fn path(&self, points: &[(f32, f32)]) -> String {
+        let mut d = String::new();
+        for (index, (x, y)) in points.iter().enumerate() {
+            let command = if index == 0 { 'M' } else { 'L' };
+            write!(d, "{command}{} {}", self.number(*x), self.number(*y)).unwrap();
+        }
+        d
+    }
+    /// Rounds to `precision` decimals without trailing zeros.
+    fn number(&self, value: f32) -> String {
+        let number = format!("{:.*}", self.precision, value);
+        let number = if number.contains('.') {
+            number.trim_end_matches('0').trim_end_matches('.')
+        } else {
+            &number
+        };
+        match number {
+            "-0" => "0".to_string(),
+            number => number.to_string(),
+        }
+    }
+}
+
+/// A pen-down run: segments joined end to start with one stroke.
+struct Run {
+    color: Rgb,
+    width: f32,
+    points: Vec<(f32, f32)>,
+}
+
+impl Run {
+    fn stroke(&self) -> (Rgb, f32) {
+        (self.color, self.width)
+    }
+}
+
+enum Shape {
+    Run(Run),
+    Arc(Arc),
+    Dot(Dot),
+    Fill(Fill),
+    Label(Label),
+}
+
+impl Shape {
+    /// The stroke a shape shares with its group; dots and fills have none.
+    fn stroke(&self) -> Option<(Rgb, f32)> {
+        match self {
+            Shape::Run(run) => Some(run.stroke()),
+            Shape::Arc(arc) => Some((arc.color(), arc.width())),
+            Shape::Dot(_) | Shape::Fill(_) | Shape::Label(_) => None,
+        }
+    }
+}
+
+/// `outline_labels` draws labels as strokes of the built-in font, with lines
+/// that thicken with the text so default-sized labels get a 1px pen.
+fn shapes(primitives: &[Primitive], outline_labels: bool) -> Vec<Shape> {
+    let mut shapes: Vec<Shape> = Vec::new();
+    for primitive in primitives {
+        let segment = match primitive {
+            Primitive::Segment(segment) => segment,
+            Primitive::Arc(arc) => {
+                shapes.push(Shape::Arc(*arc));
+                continue;
+            }
+            Primitive::Dot(dot) => {
+                shapes.push(Shape::Dot(*dot));
+                continue;
+            }
+            Primitive::Fill(fill) => {
+                shapes.push(Shape::Fill(fill.clone()));
+                continue;
+            }
+            Primitive::Label(label) if outline_labels => {
+                let width = DEFAULT_PEN_SIZE * label.height() / DEFAULT_LABEL_HEIGHT;
+                shapes.extend(label.strokes().into_iter().map(|points| {
+                    Shape::Run(Run {
+                        color: label.color(),
+                        width,
+                        points,
+                    })
+                }));
+                continue;
+            }
+            Primitive::Label(label) => {
+                shapes.push(Shape::Label(label.clone()));
+                continue;
+            }
+        };
+        match shapes.last_mut() {
+            Some(Shape::Run(run))
+                if run.stroke() == (segment.color(), segment.width())
+                    && run.points.last() == Some(&segment.from()) =>
+            {
+                run.points.push(segment.to());
+            }
+            _ => shapes.push(Shape::Run(Run {
+                color: segment.color(),
+                width: segment.width(),
+                points: vec![segment.from(), segment.to()],
+            })),
+        }
+    }
+    shapes
+}
+
+/// Escapes the characters XML gives a meaning to in text.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
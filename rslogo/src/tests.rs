@@ -1,17 +1,39 @@
 use crate::{
     ast::{
         AssignmentExpression, BinaryExpression, CallExpression, Callee, IfStatement,
-        ProcedureDeclaration, Statement, TestType, WhileStatement,
+        ProcedureDeclaration, Span, Statement, TestType, WhileStatement,
     },
+    bytecode::Op,
+    canvas::{self, Line, NullCanvas, RecordingCanvas},
     commands::{Literal, Value},
-    program::Program,
+    dap,
+    debugger::Debugger,
+    display_list::{Arc, Dot, Fill, Label, Primitive, Rgb, Segment},
+    formatter::Formatter,
+    heap::Heap,
+    linter::{Lint, Linter},
+    lsp::{Document, LanguageServer, Symbol},
+    panics::catch_quietly,
+    program::{Engine, Program},
+    repl::Repl,
+    svg::{ImageWriter, SvgWriter},
     tokens::{
         AssignmentType, CalculationOperation, CommandType, IdentifierType, LogicalOperation,
         QueryType, TokenType, ValueType,
     },
+    trace::Tracer,
+    transpiler::{SourceMap, Target},
 };
 
 use super::Image;
+use std::{
+    cell::RefCell,
+    fs,
+    io::{self, Write},
+    path::Path,
+    rc::Rc,
+};
+use unsvg::COLORS;
 
 fn init_program_code(code: &str, run: bool) -> Program {
     let image = Image::new(200, 200);
@@ -37,10 +59,23 @@ fn build_variable_assignment_expression(
 ) -> Statement {
     Statement::AssignmentExpression(AssignmentExpression::new(operation, left, right))
 }
+/// Statements serialize with their source lines, which the expected trees
+/// built here do not have.
+fn without_spans(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(object) => object
+            .into_iter()
+            .filter(|(key, _)| key != "span")
+            .map(|(key, value)| (key, without_spans(value)))
+            .collect(),
+        serde_json::Value::Array(array) => array.into_iter().map(without_spans).collect(),
+        value => value,
+    }
+}
 fn compare_statement(statements: &Statement, expected: Statement) {
     assert_eq!(
-        serde_json::to_string(statements).unwrap(),
-        serde_json::to_string(&expected).unwrap()
+        without_spans(serde_json::to_value(statements).unwrap()).to_string(),
+        without_spans(serde_json::to_value(&expected).unwrap()).to_string()
     );
 }
 fn build_value_from_query(query_type: QueryType) -> Value {
@@ -805,7 +840,7 @@ fn compare_turtle_pen(program: &Program, expected: bool) {
 fn compare_vairable_value(program: &Program, variable_name: &str, expected: Literal) {
     let heap = program.heap();
     let variable = heap
-        .variable_value(&variable_name.to_string())
+        .variable_value(variable_name)
         .expect("Variable is not found");
     assert_eq!(variable, expected);
 }
@@ -968,3 +1003,1682 @@ fn test_procedure() {
     compare_turtle_position(&program, (100.0, -20.0), true);
     compare_vairable_value(&program, "Arg", Literal::Int(90));
 }
+
+#[test]
+fn test_resolve_variable_slots() {
+    let code = "
+    MAKE \"a \"1
+    TO ADD \"b
+        ADDASSIGN \"a :b
+    END
+    ADD \"2
+    MAKE \"c + :a :b
+    ";
+    let program = init_program_code(code, true);
+    let heap = program.heap();
+    assert_eq!(heap.slot("a"), Some(0));
+    assert_eq!(heap.slot("b"), Some(1));
+    assert_eq!(heap.slot("c"), Some(2));
+    assert_eq!(heap.variable(0), Some(&Literal::Int(3)));
+    compare_vairable_value(&program, "c", Literal::Int(5));
+    match &program.statements()[3] {
+        Statement::AssignmentExpression(assign) => {
+            assert_eq!(assign.slot(), Some(2));
+            match assign.right() {
+                Value::BinaryExpression(binary) => {
+                    assert!(matches!(binary.left(), Value::Variable(v) if v.slot() == 0));
+                    assert!(matches!(binary.right(), Value::Variable(v) if v.slot() == 1));
+                }
+                _ => panic!("Expect a binary expression"),
+            }
+        }
+        _ => panic!("Expect an assignment"),
+    }
+}
+
+#[test]
+fn test_unresolved_statements_execute() {
+    let image = Image::new(200, 200);
+    let statements = vec![
+        build_variable_assignment_expression(
+            AssignmentType::Make,
+            "x".to_string(),
+            Value::Literal(ValueType::Int(10)),
+        ),
+        build_control_call_expression(
+            CommandType::Forward,
+            vec![Value::Identifier(IdentifierType::Variable("x".to_string()))],
+        ),
+    ];
+    let mut program = Program::new(statements, Heap::new(image));
+    program.run();
+    compare_vairable_value(&program, "x", Literal::Int(10));
+    compare_turtle_position(&program, (10.0, 0.0), false);
+}
+
+#[test]
+fn test_tail_recursion() {
+    let code = "
+    TO COUNT \"N
+        ADDASSIGN \"STEPS \"1
+        IF LT :N \"100000 [
+            FORWARD \"0
+            COUNT + :N \"1
+        ]
+    END
+
+    TO SPIRAL \"N
+        FORWARD \"1
+        MAKE \"LAST :N
+        IF GT :N \"0 [
+            SPIRAL - :N \"1
+        ]
+    END
+
+    MAKE \"STEPS \"0
+    COUNT \"0
+    SPIRAL \"50000
+    ";
+    let program = init_program_code(code, true);
+    compare_vairable_value(&program, "STEPS", Literal::Int(100001));
+    compare_vairable_value(&program, "LAST", Literal::Int(0));
+    compare_turtle_position(&program, (50001.0, 0.0), false);
+}
+
+#[test]
+fn test_tail_call_restores_shadowed_variables() {
+    let code = "
+    MAKE \"a \"1
+    TO INNER \"a \"b
+        MAKE \"seen + :a :b
+    END
+    TO OUTER \"a
+        INNER + :a \"1 \"5
+    END
+    OUTER \"10
+    ";
+    let program = init_program_code(code, true);
+    compare_vairable_value(&program, "a", Literal::Int(1));
+    compare_vairable_value(&program, "b", Literal::Int(5));
+    compare_vairable_value(&program, "seen", Literal::Int(16));
+}
+
+//* ****************************************************************************** //
+//*                                                                                //
+//*                             Bytecode Engine Tests                              //
+//*                                                                                //
+//* ****************************************************************************** //
+
+fn init_program_with_engine(code: &str, engine: Engine) -> Program {
+    let image = Image::new(200, 200);
+    let mut program = Program::parse_logo(&code.to_string(), image);
+    program.set_engine(engine);
+    program.run();
+    program
+}
+fn render_svg(program: &Program, name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("rslogo_{}_{}.svg", std::process::id(), name));
+    program
+        .image()
+        .save_svg(&path)
+        .expect("svg can not be saved");
+    let svg = fs::read_to_string(&path).expect("svg can not be read");
+    fs::remove_file(&path).ok();
+    svg
+}
+fn compare_engines(code: &str, name: &str) {
+    let interpreted = init_program_with_engine(code, Engine::Interpreter);
+    let compiled = init_program_with_engine(code, Engine::Bytecode);
+    assert_eq!(
+        render_svg(&interpreted, &format!("{}_interpreter", name)),
+        render_svg(&compiled, &format!("{}_bytecode", name)),
+        "images differ for {}",
+        name
+    );
+    assert_eq!(interpreted.display_list(), compiled.display_list());
+    let (expected, actual) = (interpreted.heap().turtle(), compiled.heap().turtle());
+    assert_eq!(expected.x(), actual.x());
+    assert_eq!(expected.y(), actual.y());
+    assert_eq!(expected.direction(), actual.direction());
+    assert_eq!(expected.color(), actual.color());
+    assert_eq!(expected.pen(), actual.pen());
+}
+
+#[test]
+fn test_bytecode_compile() {
+    let code = "
+    MAKE \"i \"0
+    WHILE LT :i \"3 [
+        FORWARD :i
+        ADDASSIGN \"i \"1
+    ]
+    ";
+    let mut program = init_program_code(code, false);
+    let bytecode = program.compile();
+    assert_eq!(program.heap().slot("i"), Some(0));
+    assert_eq!(
+        serde_json::to_string(bytecode.main()).unwrap(),
+        serde_json::to_string(&vec![
+            Op::Push(Literal::Int(0)),
+            Op::Store(0),
+            Op::Load(0),
+            Op::Push(Literal::Int(3)),
+            Op::Lt,
+            Op::JumpIfFalse(11),
+            Op::Load(0),
+            Op::Forward,
+            Op::Push(Literal::Int(1)),
+            Op::AddAssign(0),
+            Op::Jump(2),
+        ])
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_bytecode_variables() {
+    let code = "
+    MAKE \"Arg \"90
+    TO BOXX \"Arg \"Other
+        FORWARD :Arg
+        MAKE \"inner + :Arg :Other
+    END
+    BOXX \"50 \"5
+    MAKE \"long + + \"10 XCOR + \"10 \"10
+    ";
+    let interpreted = init_program_with_engine(code, Engine::Interpreter);
+    let compiled = init_program_with_engine(code, Engine::Bytecode);
+    for name in ["Arg", "Other", "inner", "long"] {
+        assert_eq!(
+            interpreted.heap().variable_value(name),
+            compiled.heap().variable_value(name),
+            "variable {} differs",
+            name
+        );
+    }
+    compare_vairable_value(&compiled, "Arg", Literal::Int(90));
+}
+
+#[test]
+fn test_bytecode_tail_call() {
+    let code = "
+    TO COUNT \"N
+        MAKE \"LAST :N
+        IF LT :N \"100000 [
+            COUNT + :N \"1
+        ]
+    END
+    COUNT \"0
+    ";
+    let mut program = init_program_code(code, false);
+    let bytecode = program.compile();
+    assert!(matches!(
+        bytecode.functions()[0].code()[..],
+        [.., Op::TailCall { .. }, Op::Return]
+    ));
+    let compiled = init_program_with_engine(code, Engine::Bytecode);
+    compare_vairable_value(&compiled, "LAST", Literal::Int(100000));
+}
+
+#[test]
+fn test_bytecode_matches_interpreter() {
+    let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/logo_examples");
+    let mut paths: Vec<_> = fs::read_dir(examples)
+        .expect("logo_examples is missing")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| !path.to_string_lossy().ends_with("_err.lg"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+    for path in paths {
+        let code = fs::read_to_string(&path).unwrap();
+        let name = path.file_stem().unwrap().to_string_lossy().to_string();
+        compare_engines(&code, &name);
+    }
+}
+
+//* ****************************************************************************** //
+//*                                                                                //
+//*                             REPL Tests                                         //
+//*                                                                                //
+//* ****************************************************************************** //
+
+fn run_repl(input: &str) -> String {
+    let mut repl = Repl::new(Image::new(200, 200));
+    let mut output: Vec<u8> = Vec::new();
+    repl.run(input.as_bytes(), &mut output).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn test_repl_buffers_until_balanced() {
+    let input = "PENDOWN
+TO BOX \"Arg
+    FORWARD :Arg
+    TURN \"90
+END
+MAKE \"i \"0
+WHILE LT :i \"2 [
+    BOX \"10
+    ADDASSIGN \"i \"1
+]
+";
+    let output = run_repl(input);
+    assert_eq!(
+        output,
+        "> XCOR 100 YCOR 100 HEADING 0\n\
+         > ... ... ... XCOR 100 YCOR 100 HEADING 0\n\
+         > XCOR 100 YCOR 100 HEADING 0\n\
+         > ... ... ... XCOR 110 YCOR 90 HEADING 180\n\
+         > "
+    );
+}
+
+#[test]
+fn test_repl_waits_for_a_block_on_the_next_line() {
+    let input = "MAKE \"x \"0
+WHILE LT :x \"5
+[
+    ADDASSIGN \"x \"1
+]
+.variables
+";
+    let output = run_repl(input);
+    assert_eq!(
+        output,
+        "> XCOR 100 YCOR 100 HEADING 0\n\
+         > ... ... ... XCOR 100 YCOR 100 HEADING 0\n\
+         > x = 5\n\
+         > "
+    );
+}
+
+#[test]
+fn test_repl_commands() {
+    let input = "TO BOXX \"Arg \"Size
+FORWARD :Arg
+END
+MAKE \"b \"TRUE
+MAKE \"a \"1.5
+.procedures
+.variables
+.quit
+FORWARD \"10
+";
+    let output = run_repl(input);
+    assert!(output.contains("> BOXX :Arg :Size\n"));
+    assert!(output.contains("> a = 1.5\nb = true\n"));
+    assert!(!output.contains("YCOR 90"));
+}
+
+//* ****************************************************************************** //
+//*                                                                                //
+//*                             Debugger Tests                                     //
+//*                                                                                //
+//* ****************************************************************************** //
+
+/// Output sink that stays readable after the debugger is handed to the heap.
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+const DEBUG_CODE: &str = "TO BOX \"len
+    FORWARD :len
+    LEFT :len
+END
+MAKE \"x \"10
+BOX :x
+BOX \"5
+";
+
+fn run_debugger(commands: &'static str) -> String {
+    let output = SharedBuffer::default();
+    let mut program = init_program_code(DEBUG_CODE, false);
+    program.set_hook(Box::new(Debugger::new(
+        DEBUG_CODE,
+        commands.as_bytes(),
+        output.clone(),
+    )));
+    program.run();
+    let text = String::from_utf8(output.0.borrow().clone()).unwrap();
+    text
+}
+
+#[test]
+fn test_statement_spans() {
+    let program = init_program_code(DEBUG_CODE, false);
+    let spans: Vec<(usize, usize)> = program
+        .statements()
+        .iter()
+        .map(|statement| (statement.span().start(), statement.span().end()))
+        .collect();
+    assert_eq!(spans, vec![(1, 4), (5, 5), (6, 6), (7, 7)]);
+}
+
+#[test]
+fn test_debugger_breakpoints_and_stepping() {
+    let output = run_debugger(
+        "break 3
+continue
+backtrace
+print len
+turtle
+delete 3
+next
+step
+finish
+",
+    );
+    assert!(output.starts_with("Stopped at line 1 in main: TO BOX \"len\n"));
+    assert!(output.contains("Stopped at line 3 in BOX: LEFT :len\n"));
+    assert!(output.contains("#0 BOX at line 3\n#1 main at line 6\n"));
+    assert!(output.contains("len = 10\n"));
+    assert!(output.contains("x = 100 y = 90 direction = 0 pen = up color = 7\n"));
+    assert!(output.contains("Stopped at line 7 in main: BOX \"5\n"));
+    assert!(output.contains("Stopped at line 2 in BOX: FORWARD :len\n"));
+    assert_eq!(output.matches("Stopped at").count(), 4);
+}
+
+#[test]
+fn test_debugger_procedure_breakpoint() {
+    let output = run_debugger(
+        "break BOX
+continue
+print
+continue
+continue
+",
+    );
+    assert_eq!(output.matches("Stopped at line 2 in BOX").count(), 2);
+    assert!(output.contains("len = 10\nx = 10\n"));
+}
+
+fn dap_request(seq: usize, command: &str, arguments: serde_json::Value) -> String {
+    let body = serde_json::json!({
+        "seq": seq,
+        "type": "request",
+        "command": command,
+        "arguments": arguments,
+    })
+    .to_string();
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+}
+
+fn read_messages(output: &str) -> Vec<serde_json::Value> {
+    output
+        .split("Content-Length: ")
+        .filter(|message| !message.is_empty())
+        .map(|message| {
+            let (_, body) = message.split_once("\r\n\r\n").unwrap();
+            serde_json::from_str(body).unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn test_dap_session() {
+    let path = std::env::temp_dir().join(format!("rslogo_{}_dap.lg", std::process::id()));
+    fs::write(&path, DEBUG_CODE).unwrap();
+    let requests = [
+        dap_request(1, "initialize", serde_json::json!({})),
+        dap_request(2, "launch", serde_json::json!({ "program": path })),
+        dap_request(
+            3,
+            "setBreakpoints",
+            serde_json::json!({ "breakpoints": [{ "line": 3 }, { "line": 4 }] }),
+        ),
+        dap_request(4, "configurationDone", serde_json::json!({})),
+        dap_request(5, "stackTrace", serde_json::json!({ "threadId": 1 })),
+        dap_request(6, "scopes", serde_json::json!({ "frameId": 1 })),
+        dap_request(
+            7,
+            "variables",
+            serde_json::json!({ "variablesReference": 4 }),
+        ),
+        dap_request(8, "next", serde_json::json!({})),
+        dap_request(
+            9,
+            "variables",
+            serde_json::json!({ "variablesReference": 2 }),
+        ),
+        dap_request(10, "continue", serde_json::json!({})),
+        dap_request(11, "continue", serde_json::json!({})),
+        dap_request(12, "disconnect", serde_json::json!({})),
+    ]
+    .concat();
+    let output = SharedBuffer::default();
+    dap::serve(std::io::Cursor::new(requests.into_bytes()), output.clone()).unwrap();
+    fs::remove_file(&path).ok();
+    let text = String::from_utf8(output.0.borrow().clone()).unwrap();
+    let messages = read_messages(&text);
+
+    let response = |seq: usize| {
+        messages
+            .iter()
+            .find(|message| message["request_seq"] == seq)
+            .unwrap()
+    };
+    let verified = &response(3)["body"]["breakpoints"];
+    assert_eq!(verified[0]["verified"], true);
+    assert_eq!(verified[1]["verified"], false);
+    let frames = &response(5)["body"]["stackFrames"];
+    assert_eq!(frames[0]["name"], "BOX");
+    assert_eq!(frames[0]["line"], 3);
+    assert_eq!(frames[1]["name"], "main");
+    assert_eq!(frames[1]["line"], 6);
+    assert_eq!(response(6)["body"]["scopes"][0]["name"], "Arguments");
+    assert_eq!(response(7)["body"]["variables"][0]["name"], "len");
+    assert_eq!(response(7)["body"]["variables"][0]["value"], "10");
+    assert_eq!(response(9)["body"]["variables"][1]["value"], "240");
+
+    let events: Vec<String> = messages
+        .iter()
+        .filter(|message| message["type"] == "event")
+        .map(|message| match message["body"]["reason"].as_str() {
+            Some(reason) => format!("{}:{}", message["event"].as_str().unwrap(), reason),
+            None => message["event"].as_str().unwrap().to_string(),
+        })
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            "initialized",
+            "stopped:breakpoint",
+            "stopped:step",
+            "stopped:breakpoint",
+            "exited",
+            "terminated"
+        ]
+    );
+}
+
+#[test]
+fn test_dap_recursive_frame_arguments() {
+    let code = "TO DOWN \"n
+IF GT :n \"0 [
+    DOWN - :n \"1
+]
+FORWARD :n
+END
+DOWN \"2
+";
+    let path = std::env::temp_dir().join(format!("rslogo_{}_dap_frames.lg", std::process::id()));
+    fs::write(&path, code).unwrap();
+    let arguments = |seq: usize, frame: usize| {
+        dap_request(
+            seq,
+            "variables",
+            serde_json::json!({ "variablesReference": 3 + frame }),
+        )
+    };
+    let requests = [
+        dap_request(1, "initialize", serde_json::json!({})),
+        dap_request(2, "launch", serde_json::json!({ "program": path })),
+        dap_request(
+            3,
+            "setBreakpoints",
+            serde_json::json!({ "breakpoints": [{ "line": 5 }] }),
+        ),
+        dap_request(4, "configurationDone", serde_json::json!({})),
+        dap_request(5, "stackTrace", serde_json::json!({ "threadId": 1 })),
+        arguments(6, 1),
+        arguments(7, 2),
+        arguments(8, 3),
+        dap_request(9, "disconnect", serde_json::json!({})),
+    ]
+    .concat();
+    let output = SharedBuffer::default();
+    dap::serve(std::io::Cursor::new(requests.into_bytes()), output.clone()).unwrap();
+    fs::remove_file(&path).ok();
+    let text = String::from_utf8(output.0.borrow().clone()).unwrap();
+    let messages = read_messages(&text);
+    let response = |seq: usize| {
+        messages
+            .iter()
+            .find(|message| message["request_seq"] == seq)
+            .unwrap()
+    };
+    let frames = response(5)["body"]["stackFrames"].as_array().unwrap();
+    assert_eq!(frames.len(), 4);
+    assert_eq!(frames[1]["line"], 3);
+    let n = |seq: usize| &response(seq)["body"]["variables"][0]["value"];
+    assert_eq!(n(6), "2");
+    assert_eq!(n(7), "1");
+    assert_eq!(n(8), "0");
+}
+
+//* ****************************************************************************** //
+//*                                                                                //
+//*                             Language Server Tests                              //
+//*                                                                                //
+//* ****************************************************************************** //
+
+fn lsp_message(id: Option<usize>, method: &str, params: serde_json::Value) -> String {
+    let mut message = serde_json::json!({ "jsonrpc": "2.0", "method": method, "params": params });
+    if let Some(id) = id {
+        message["id"] = serde_json::json!(id);
+    }
+    let body = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+}
+
+#[test]
+fn test_lsp_session() {
+    let uri = "file:///box.lg";
+    let text = "TO BOX \"len\nFORWARD :len\nEND\nMAKE \"size \"10\nBOX :size\nFORWARD\n";
+    let document = serde_json::json!({ "uri": uri });
+    let at = |line: usize, character: usize| serde_json::json!({ "textDocument": document, "position": { "line": line, "character": character } });
+    let mut rename = at(3, 7);
+    rename["newName"] = serde_json::json!("length");
+    let requests = [
+        lsp_message(Some(1), "initialize", serde_json::json!({})),
+        lsp_message(None, "initialized", serde_json::json!({})),
+        lsp_message(
+            None,
+            "textDocument/didOpen",
+            serde_json::json!({ "textDocument": { "uri": uri, "languageId": "logo", "version": 1, "text": text } }),
+        ),
+        lsp_message(Some(2), "textDocument/definition", at(4, 1)),
+        lsp_message(Some(3), "textDocument/references", at(1, 10)),
+        lsp_message(Some(4), "textDocument/hover", at(4, 0)),
+        lsp_message(Some(5), "textDocument/completion", at(5, 0)),
+        lsp_message(Some(6), "textDocument/rename", rename),
+        lsp_message(
+            None,
+            "textDocument/didChange",
+            serde_json::json!({
+                "textDocument": { "uri": uri, "version": 2 },
+                "contentChanges": [{
+                    "range": { "start": { "line": 5, "character": 7 }, "end": { "line": 5, "character": 7 } },
+                    "text": " :size",
+                }],
+            }),
+        ),
+        lsp_message(Some(7), "shutdown", serde_json::json!(null)),
+        lsp_message(None, "exit", serde_json::json!(null)),
+    ]
+    .concat();
+    let output = SharedBuffer::default();
+    LanguageServer::new(std::io::Cursor::new(requests.into_bytes()), output.clone())
+        .serve()
+        .unwrap();
+    let text = String::from_utf8(output.0.borrow().clone()).unwrap();
+    let messages = read_messages(&text);
+    let result =
+        |id: usize| &messages.iter().find(|message| message["id"] == id).unwrap()["result"];
+    let range = |line: usize, start: usize, end: usize| serde_json::json!({ "start": { "line": line, "character": start }, "end": { "line": line, "character": end } });
+
+    let diagnostics: Vec<&serde_json::Value> = messages
+        .iter()
+        .filter(|message| message["method"] == "textDocument/publishDiagnostics")
+        .map(|message| &message["params"]["diagnostics"])
+        .collect();
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].as_array().unwrap().len(), 1);
+    assert_eq!(diagnostics[0][0]["range"], range(5, 0, 7));
+    assert_eq!(diagnostics[1], &serde_json::json!([]));
+
+    assert_eq!(result(2)[0]["range"], range(0, 3, 6));
+    let references: Vec<&serde_json::Value> = result(3)
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|location| &location["range"])
+        .collect();
+    assert_eq!(references, vec![&range(0, 8, 11), &range(1, 9, 12)]);
+    assert_eq!(result(4)["contents"]["value"], "```logo\nTO BOX :len\n```");
+    let labels: Vec<&str> = result(5)
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["label"].as_str().unwrap())
+        .collect();
+    assert!(labels.contains(&"FORWARD"));
+    assert!(labels.contains(&"HEADING"));
+    assert!(labels.contains(&"BOX"));
+    let edits = result(6)["changes"][uri].as_array().unwrap();
+    assert_eq!(edits.len(), 2);
+    assert_eq!(edits[0]["range"], range(3, 6, 10));
+    assert_eq!(edits[1]["range"], range(4, 5, 9));
+}
+
+#[test]
+fn test_lsp_parameters_are_scoped_to_their_procedure() {
+    let document = Document::new(
+        "MAKE \"len \"5
+TO BOX \"len
+FORWARD :len
+END
+TO LINE \"len
+BACK :len
+END
+FORWARD :len
+"
+        .to_string(),
+    );
+    let lines = |symbol: &Symbol, definitions: bool| -> Vec<usize> {
+        let symbols: Vec<&Symbol> = if definitions {
+            document.definitions(symbol).collect()
+        } else {
+            document.occurrences(symbol).collect()
+        };
+        symbols.iter().map(|other| other.range().line()).collect()
+    };
+    let parameter = document.symbol_at(2, 9).unwrap();
+    assert_eq!(lines(parameter, false), vec![1, 2]);
+    assert_eq!(lines(parameter, true), vec![1]);
+    let global = document.symbol_at(7, 9).unwrap();
+    assert_eq!(lines(global, false), vec![0, 7]);
+    assert_eq!(lines(global, true), vec![0]);
+}
+
+//* ****************************************************************************** //
+//*                                                                                //
+//*                             Trace Tests                                        //
+//*                                                                                //
+//* ****************************************************************************** //
+
+#[test]
+fn test_trace_records_commands() {
+    let code = "PENDOWN
+MAKE \"d \"10
+
+FORWARD + :d \"5
+SETX XCOR
+";
+    let output = SharedBuffer::default();
+    let mut program = init_program_code(code, false);
+    program.set_hook(Box::new(Tracer::new(output.clone())));
+    program.run();
+    let text = String::from_utf8(output.0.borrow().clone()).unwrap();
+    let records: Vec<serde_json::Value> = text
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(records.len(), 4);
+    assert_eq!(
+        records[0],
+        serde_json::json!({
+            "line": 1,
+            "kind": "Command",
+            "callee": { "Command": "Pendown" },
+            "arguments": [],
+            "before": { "x": 100.0, "y": 100.0, "heading": 0, "pen": false, "color": 7 },
+            "after": { "x": 100.0, "y": 100.0, "heading": 0, "pen": true, "color": 7 },
+        })
+    );
+    assert_eq!(records[1]["line"], 4);
+    assert_eq!(records[1]["arguments"], serde_json::json!([{ "Int": 15 }]));
+    assert_eq!(records[1]["after"]["y"], 85.0);
+    assert_eq!(records[2]["kind"], "Query");
+    assert_eq!(records[2]["callee"], serde_json::json!({ "Query": "Xcor" }));
+    assert_eq!(records[3]["line"], 5);
+    assert_eq!(
+        records[3]["arguments"],
+        serde_json::json!([{ "Float": 100.0 }])
+    );
+}
+
+#[test]
+fn test_trace_is_written_before_an_error() {
+    let code = "PENDOWN
+FORWARD \"10
+FORWARD :missing
+";
+    let output = SharedBuffer::default();
+    let mut program = init_program_code(code, false);
+    let writer = io::BufWriter::with_capacity(1 << 16, output.clone());
+    program.set_hook(Box::new(Tracer::new(writer)));
+    assert!(catch_quietly(|| program.run()).is_err());
+    // The program is still alive, so nothing has been flushed by dropping it.
+    let text = String::from_utf8(output.0.borrow().clone()).unwrap();
+    assert_eq!(text.lines().count(), 2);
+    assert!(text.ends_with("\n"));
+}
+
+//* ****************************************************************************** //
+//*                                                                                //
+//*                             Formatter Tests                                    //
+//*                                                                                //
+//* ****************************************************************************** //
+
+#[test]
+fn test_format_indents_and_keeps_comments() {
+    let code = "// draw boxes
+TO BOX \"Arg :Size
+  FORWARD   :Arg
+       // turn around
+  TURN \"90.0
+
+
+END
+MAKE \"i \"0
+WHILE LT :i \"2 [
+BOX \"10 \"1.50
+IF AND EQ :i \"1 \"true [
+PENUP
+SETX \"3.14159265
+    ]
+  ADDASSIGN \"i + \"1 XCOR
+    // done
+]
+// trailing
+";
+    let expected = "// draw boxes
+TO BOX \"Arg \"Size
+    FORWARD :Arg
+    // turn around
+    TURN \"90.0
+END
+MAKE \"i \"0
+WHILE LT :i \"2 [
+    BOX \"10 \"1.50
+    IF AND EQ :i \"1 \"true [
+        PENUP
+        SETX \"3.14159265
+    ]
+    ADDASSIGN \"i + \"1 XCOR
+    // done
+]
+// trailing
+";
+    assert_eq!(Formatter::format(&code.to_string()), expected);
+}
+
+#[test]
+fn test_format_examples_round_trip() {
+    let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/logo_examples");
+    for entry in fs::read_dir(examples).expect("logo_examples is missing") {
+        let path = entry.unwrap().path();
+        let name = path.file_stem().unwrap().to_str().unwrap().to_string();
+        if name.ends_with("_err") {
+            continue;
+        }
+        let code = fs::read_to_string(&path).unwrap();
+        let formatted = Formatter::format(&code);
+        assert_eq!(Formatter::format(&formatted), formatted, "{}", name);
+        let original = init_program_code(&code, true);
+        let reformatted = init_program_code(&formatted, true);
+        assert_eq!(
+            render_svg(&original, &format!("{}_fmt_a", name)),
+            render_svg(&reformatted, &format!("{}_fmt_b", name)),
+            "{}",
+            name
+        );
+    }
+}
+
+//* ****************************************************************************** //
+//*                                                                                //
+//*                             Linter Tests                                       //
+//*                                                                                //
+//* ****************************************************************************** //
+
+fn lint_codes(code: &str) -> Vec<(Lint, usize)> {
+    Linter::lint(&code.to_string())
+        .iter()
+        .map(|warning| (warning.lint(), warning.line()))
+        .collect()
+}
+
+#[test]
+fn test_lint_warnings() {
+    let code = r#"MAKE "size "10
+MAKE "unused "1
+FORWARD :missing
+ADDASSIGN "count "1
+TO DRAW "size
+FORWARD :size
+FORWARD :later
+END
+TO SPARE
+PENUP
+END
+DRAW "5
+MAKE "later "2
+MAKE "i "0
+WHILE LT :i :size [
+FORWARD "1
+]
+WHILE LT XCOR "100 [
+FORWARD "1
+]
+"#;
+    assert_eq!(
+        lint_codes(code),
+        vec![
+            (Lint::UnusedVariable, 2),
+            (Lint::UndefinedVariable, 3),
+            (Lint::UndefinedAddassign, 4),
+            (Lint::ShadowedGlobal, 5),
+            (Lint::UnusedProcedure, 9),
+            (Lint::UnchangedCondition, 15),
+        ]
+    );
+}
+
+#[test]
+fn test_lint_follows_procedures_and_allow_comments() {
+    let code = r#"TO SETUP
+MAKE "i "0
+END
+TO STEP
+ADDASSIGN "i "1
+STEP
+END
+SETUP
+// lint: allow L003
+MAKE "unused "1
+WHILE LT :i "3 [
+STEP
+]
+"#;
+    assert_eq!(lint_codes(code), vec![]);
+}
+
+//* ****************************************************************************** //
+//*                                                                                //
+//*                             AST JSON Tests                                     //
+//*                                                                                //
+//* ****************************************************************************** //
+
+#[test]
+fn test_ast_json_round_trip() {
+    let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/logo_examples");
+    for entry in fs::read_dir(examples).expect("logo_examples is missing") {
+        let path = entry.unwrap().path();
+        let name = path.file_stem().unwrap().to_str().unwrap().to_string();
+        if name.ends_with("_err") {
+            continue;
+        }
+        let code = fs::read_to_string(&path).unwrap();
+        let original = init_program_code(&code, true);
+        let ast = serde_json::to_string(original.statements()).unwrap();
+        let mut program = Program::parse_ast(&ast, Image::new(200, 200)).unwrap();
+        assert_eq!(serde_json::to_string(program.statements()).unwrap(), ast);
+        let spans = |program: &Program| -> Vec<Span> {
+            program.statements().iter().map(Statement::span).collect()
+        };
+        assert_eq!(spans(&program), spans(&original), "{}", name);
+        assert!(
+            spans(&program).iter().all(|span| span.start() > 0),
+            "{}",
+            name
+        );
+        program.run();
+        assert_eq!(
+            render_svg(&original, &format!("{}_ast_a", name)),
+            render_svg(&program, &format!("{}_ast_b", name)),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_ast_json_from_front_end() {
+    let ast = r#"[
+        {"AssignmentExpression": {"operation": "Make", "left": "len", "right": {"Literal": {"Int": 30}}}},
+        {"CallExpression": {"callee": {"Command": "Pendown"}, "arguments": []}},
+        {"CallExpression": {"callee": {"Command": "Forward"}, "arguments": [{"Identifier": {"Variable": "len"}}]}}
+    ]"#;
+    let mut program = Program::parse_ast(ast, Image::new(200, 200)).unwrap();
+    program.run();
+    assert_eq!(program.heap().turtle().y(), 70.0);
+    assert!(program.emit(Target::Python).contains("forward"));
+    assert!(Program::parse_ast(r#"[{"While": {}}]"#, Image::new(200, 200)).is_err());
+}
+
+//* ****************************************************************************** //
+//*                                                                                //
+//*                             Python Transpiler Tests                            //
+//*                                                                                //
+//* ****************************************************************************** //
+
+#[test]
+fn test_python_runnable_output() {
+    let code = r#"MAKE "steps "0
+TO STAR "len
+MAKE "steps + :steps "1
+IF AND GT :len "5 OR EQ COLOR "7 EQ "TRUE "FALSE [
+FORWARD / :len "2.0
+LEFT / :len "2
+]
+IF EQ XCOR "0 [
+]
+END
+STAR "20
+"#;
+    let program = init_program_code(code, false);
+    let python = program.emit(Target::Python);
+    let empty = init_program_code("", false).emit(Target::Python);
+    let (prelude, epilogue) = empty.split_at(empty.find("\nturtle.update()").unwrap());
+    assert!(python.starts_with(prelude) && python.ends_with(epilogue));
+    assert!(prelude.starts_with("import math\nimport struct\nimport turtle\n"));
+    assert!(prelude.contains("WIDTH, HEIGHT = 200, 200\n"));
+    assert!(prelude.contains("    \"#a52a2a\",\n"));
+    assert_eq!(
+        &python[prelude.len()..python.len() - epilogue.len()],
+        r#"_vars["steps"] = 0
+def p_STAR(*args):
+    saved = _bind(["len"], args)
+    _vars["steps"] = (_vars["steps"] + 1)
+    if ((_vars["len"] > 5) and ((color() == 7) or (True == False))):
+        forward(_div(_vars["len"], 2.0))
+        left(_div(_vars["len"], 2))
+    if (xcor() == 0):
+        pass
+    _restore(saved)
+
+p_STAR(20)
+"#
+    );
+}
+
+/// A stand-in for the `turtle` module that prints the lines drawn instead of
+/// opening a window.
+const FAKE_TURTLE: &str = r#"_position = (0.0, 0.0)
+_down = False
+_lines = []
+
+
+def goto(x, y):
+    global _position
+    if _down:
+        _lines.append(_position + (x, y))
+    _position = (x, y)
+
+
+def pendown():
+    global _down
+    _down = True
+
+
+def penup():
+    global _down
+    _down = False
+
+
+def isdown():
+    return _down
+
+
+def done():
+    for line in _lines:
+        print(*map(repr, line))
+
+
+def __getattr__(name):
+    return lambda *args, **kwargs: None
+"#;
+
+/// Runs the Python a program transpiles to and returns the lines it drew in
+/// Logo's coordinates.
+fn run_python(program: &Program, name: &str) -> Vec<((f32, f32), (f32, f32))> {
+    let (width, height) = program.display_list().dimensions();
+    let dir = std::env::temp_dir().join(format!("rslogo_{}_{}_py", std::process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("turtle.py"), FAKE_TURTLE).unwrap();
+    fs::write(dir.join("main.py"), program.emit(Target::Python)).unwrap();
+    let output = std::process::Command::new("python3")
+        .arg("main.py")
+        .current_dir(&dir)
+        .output()
+        .expect("python3 is needed to run the transpiled program");
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let (x, y) = (width as f64 / 2.0, height as f64 / 2.0);
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let n: Vec<f64> = line.split(' ').map(|n| n.parse().unwrap()).collect();
+            (
+                ((n[0] + x) as f32, (y - n[1]) as f32),
+                ((n[2] + x) as f32, (y - n[3]) as f32),
+            )
+        })
+        .collect()
+}
+
+fn drawn_lines(program: &Program) -> Vec<((f32, f32), (f32, f32))> {
+    program
+        .display_list()
+        .segments()
+        .map(|segment| (segment.from(), segment.to()))
+        .collect()
+}
+
+#[test]
+fn test_python_draws_example_like_rslogo() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/logo_examples/4_09_spiral.lg");
+    let code = fs::read_to_string(path).unwrap();
+    let mut program = Program::parse_logo(&code, Image::new(300, 300));
+    program.run();
+    assert_eq!(program.display_list().segments().count(), 306);
+    assert_eq!(run_python(&program, "spiral"), drawn_lines(&program));
+}
+
+#[test]
+fn test_python_scopes_parameters_dynamically() {
+    let code = r#"TO INNER
+FORWARD :len
+END
+TO OUTER "len
+INNER
+END
+MAKE "color "3
+MAKE "len "5
+PENDOWN
+OUTER "10
+FORWARD :color
+FORWARD :len
+"#;
+    let program = init_program_code(code, true);
+    assert_eq!(program.display_list().segments().count(), 3);
+    assert_eq!(run_python(&program, "scope"), drawn_lines(&program));
+}
+
+#[test]
+fn test_js_canvas_output() {
+    let code = r#"TO STEP "len
+MAKE "total + :total :len
+IF AND GT :len "5 NE COLOR "7 [
+FORWARD / :len "2
+]
+END
+MAKE "total "0
+WHILE LT :total "20 [
+STEP "5.5
+]
+"#;
+    let html = init_program_code(code, false).emit(Target::Js);
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<canvas id=\"canvas\" width=\"200\" height=\"200\"></canvas>"));
+    assert!(html.contains("const COLORS = [\"#000000\", \"#0000ff\""));
+    let script = &html[html.find("\n};\n\n").unwrap() + 5..];
+    assert_eq!(
+        script,
+        r#"procedures.STEP = function (...args) {
+    const saved = bind(["len"], args);
+    vars.total = (vars.total + vars.len);
+    if ((vars.len > 5) && (turtle.getcolor() !== 7)) {
+        turtle.forward(div(vars.len, 2));
+    }
+    restore(saved);
+};
+vars.total = 0;
+while (vars.total < 20) {
+    procedures.STEP(5.5);
+}
+</script>
+</body>
+</html>
+"#
+    );
+}
+
+#[test]
+fn test_rust_program_output() {
+    let code = r#"TO STEP "len
+MAKE "total + :total :len
+IF AND GT :len "5 NE COLOR "7 [
+FORWARD / :len "2
+]
+END
+MAKE "total "0
+WHILE LT :total "20 [
+STEP "5.5
+]
+"#;
+    let source = init_program_code(code, false).emit(Target::Rust);
+    assert!(source.starts_with("// Generated by rslogo. Depends on unsvg = \"=1.1.2\".\n"));
+    let program = &source[source.find("\n/// Variable slots").unwrap() + 1..];
+    assert_eq!(
+        program,
+        r#"/// Variable slots: 0 = total, 1 = len
+const VARIABLES: usize = 2;
+
+/// TO STEP
+fn procedure_0(s: &mut State, args: &[Value]) {
+    let saved = s.bind(&[1], args);
+    s.set(0, add(s.get(0), s.get(1)));
+    if gt(s.get(1), Value::Int(5)) & !eq(s.color(), Value::Int(7)) {
+        s.forward(div(s.get(1), Value::Int(2)));
+    }
+    s.restore(saved);
+}
+
+fn main() {
+    let s = &mut State::new(200, 200);
+    s.set(0, Value::Int(0));
+    while lt(s.get(0), Value::Int(20)) {
+        { let args = [Value::Float(5.5)]; procedure_0(s, &args) };
+    }
+    let path = std::env::args().nth(1).unwrap_or_else(|| "output.svg".to_string());
+    s.image.save_svg(&path).expect("Error saving svg");
+}
+"#
+    );
+}
+
+#[test]
+fn test_ucblogo_output() {
+    let code = r#"TO STEP "len
+MAKE "total + :total :len
+IF AND GT :len "5 NE COLOR "7 [
+LEFT / :len "2
+]
+END
+MAKE "total "0
+SETX "20
+SETPENCOLOR "2
+WHILE LT :total "20 [
+STEP "5.5
+ADDASSIGN "total * YCOR "2
+TURN "-15
+SETHEADING HEADING
+]
+"#;
+    let source = init_program_code(code, false).emit(Target::UcbLogo);
+    assert_eq!(
+        source,
+        r#"; Translated by rslogo for a 200x200 canvas
+CLEARSCREEN
+SETBACKGROUND 0
+SETPC 7
+PENUP
+
+TO STEP :len
+  MAKE "total :total + :len
+  IF AND (:len > 5) ((ITEM PENCOLOR + 1 [0 1 3 2 4 5 6 7 8 9 10 11 12 13 14 15]) <> 7) [
+    LEFT 90 FORWARD (:len / 2) RIGHT 90
+  ]
+END
+
+MAKE "total 0
+IFELSE PENDOWNP [PENUP SETX -80 PENDOWN] [SETX -80]
+SETPC 3
+WHILE [:total < 20] [
+  STEP 5.5
+  MAKE "total :total + ((100 - YCOR) * 2)
+  RIGHT -15
+  SETH HEADING
+]
+"#
+    );
+}
+
+#[test]
+fn test_source_maps_follow_statements() {
+    let code = r#"TO STEP "len
+FORWARD :len
+END
+MAKE "total "0
+WHILE LT :total "20 [
+STEP "5
+ADDASSIGN "total "5
+]
+"#;
+    let program = init_program_code(code, false);
+    for target in [Target::Python, Target::Js, Target::Rust, Target::UcbLogo] {
+        let (source, map) = program.emit_mapped(target);
+        let lines: Vec<&str> = source.lines().collect();
+        let generated = |source_line: usize| {
+            let mapping = map
+                .mappings()
+                .into_iter()
+                .find(|mapping| mapping.source_start() == source_line)
+                .unwrap();
+            lines[mapping.generated_start() - 1..mapping.generated_end()].join("\n")
+        };
+        assert!(generated(1).to_lowercase().contains("step"), "{:?}", target);
+        assert!(
+            generated(2).to_lowercase().contains("forward"),
+            "{:?}",
+            target
+        );
+        assert_eq!(generated(4).lines().count(), 1, "{:?}", target);
+        let loop_text = generated(5);
+        assert!(loop_text.to_lowercase().contains("while"), "{:?}", target);
+        assert!(loop_text.contains(&generated(7)), "{:?}", target);
+        assert!(!loop_text.contains(&generated(2)), "{:?}", target);
+    }
+}
+
+#[test]
+fn test_source_map_formats() {
+    let mut map = SourceMap::new();
+    let buf = "while x:\n    forward(1)\n    x = 2\n";
+    map.record(Span::new(3, 6), buf, 0);
+    map.record(Span::new(4, 4), &buf[..24], 9);
+    map.record(Span::new(0, 0), buf, 26);
+    assert_eq!(
+        map.to_json_lines(),
+        "{\"generated_start\":1,\"generated_end\":3,\"source_start\":3,\"source_end\":6}\n\
+         {\"generated_start\":2,\"generated_end\":2,\"source_start\":4,\"source_end\":4}\n"
+    );
+    let v3: serde_json::Value = serde_json::from_str(&map.to_v3("a.lg")).unwrap();
+    assert_eq!(
+        v3,
+        serde_json::json!({
+            "version": 3,
+            "sources": ["a.lg"],
+            "names": [],
+            "mappings": "AAEA;AACA;AADA",
+        })
+    );
+}
+
+//* ****************************************************************************** //
+//*                                                                                //
+//*                                    Canvas                                      //
+//*                                                                                //
+//* ****************************************************************************** //
+
+#[test]
+fn test_recording_canvas_lines() {
+    let code = r#"FORWARD "10
+PENDOWN
+SETHEADING "90
+FORWARD "20
+PENUP
+BACK "5
+PENDOWN
+SETPENCOLOR "1
+RIGHT "10
+"#;
+    let canvas = RecordingCanvas::new(200, 200);
+    let lines = canvas.lines();
+    let mut program = Program::parse_with_canvas(&code.to_string(), Box::new(canvas));
+    program.run();
+    let lines = lines.borrow();
+    assert_eq!(
+        *lines,
+        vec![
+            Line::new((100.0, 90.0), 90, 20.0, COLORS[7]),
+            Line::new((115.0, 90.0), 180, 10.0, COLORS[1]),
+        ]
+    );
+    assert_eq!(lines[0].to(), (120.0, 90.0));
+    assert_eq!(lines[1].to(), (115.0, 100.0));
+}
+
+#[test]
+fn test_null_canvas_runs_headless() {
+    let code = fs::read_to_string("logo_examples/5_04_star.lg").unwrap();
+    let mut headless = Program::parse_with_canvas(&code, Box::new(NullCanvas::new(200, 200)));
+    headless.run();
+    let drawn = init_program_code(&code, true);
+    let (expected, actual) = (drawn.heap().turtle(), headless.heap().turtle());
+    assert_eq!(expected.x(), actual.x());
+    assert_eq!(expected.y(), actual.y());
+    assert_eq!(expected.direction(), actual.direction());
+    assert!(headless.heap().turtle().canvas().image().is_none());
+    assert!(headless.save_image(Path::new("never.svg")).is_err());
+}
+
+#[test]
+fn test_display_list_records_segments() {
+    let code = r#"FORWARD "10
+PENDOWN
+SETHEADING "90
+FORWARD "20
+PENUP
+BACK "5
+PENDOWN
+SETPENCOLOR "1
+SETX "50.3
+RIGHT "10
+"#;
+    let mut program =
+        Program::parse_with_canvas(&code.to_string(), Box::new(NullCanvas::new(200, 200)));
+    program.run();
+    let display_list = program.display_list();
+    assert_eq!(display_list.dimensions(), (200, 200));
+    let segments: Vec<&Segment> = display_list.segments().collect();
+    assert_eq!(
+        segments,
+        vec![
+            &Segment::new((100.0, 90.0), (120.0, 90.0), Rgb::new(255, 255, 255), 1.0),
+            &Segment::new(
+                (12877.0 / 256.0, 90.0),
+                (12877.0 / 256.0, 100.0),
+                Rgb::new(0, 0, 255),
+                1.0
+            ),
+        ]
+    );
+    let json: serde_json::Value = serde_json::from_str(&display_list.to_json()).unwrap();
+    assert_eq!(json["width"], 200);
+    assert_eq!(
+        json["primitives"][0],
+        serde_json::json!({
+            "Segment": {
+                "from": [100.0, 90.0],
+                "to": [120.0, 90.0],
+                "color": { "red": 255, "green": 255, "blue": 255 },
+                "width": 1.0,
+            }
+        })
+    );
+}
+
+//* ****************************************************************************** //
+//*                                                                                //
+//*                                      SVG                                       //
+//*                                                                                //
+//* ****************************************************************************** //
+
+#[test]
+fn test_native_svg_merges_runs() {
+    let code = r#"PENDOWN
+FORWARD "10
+TURN "90
+FORWARD "10
+SETPENCOLOR "4
+FORWARD "5
+PENUP
+FORWARD "5
+PENDOWN
+FORWARD "5
+SETPENCOLOR "7
+SETHEADING "45
+FORWARD "3
+"#;
+    let mut program =
+        Program::parse_with_canvas(&code.to_string(), Box::new(NullCanvas::new(40, 40)));
+    program.run();
+    let svg = SvgWriter::new(2).write(program.display_list());
+    assert_eq!(
+        svg,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="40" height="40" viewBox="0 0 40 40">
+<rect width="40" height="40"/>
+<g fill="none" stroke="#ffffff" stroke-width="1" stroke-linecap="round" stroke-linejoin="round">
+<path d="M20 20L20 10L30 10"/>
+</g>
+<g fill="none" stroke="#ff0000" stroke-width="1" stroke-linecap="round" stroke-linejoin="round">
+<path d="M30 10L35 10"/>
+<path d="M40 10L45 10"/>
+</g>
+<g fill="none" stroke="#ffffff" stroke-width="1" stroke-linecap="round" stroke-linejoin="round">
+<path d="M45 10L47.12 7.88"/>
+</g>
+</svg>
+"##
+    );
+    let exact = SvgWriter::new(8).write(program.display_list());
+    assert!(exact.contains(r#"<path d="M45 10L47.12109375 7.87890625"/>"#));
+}
+
+#[test]
+fn test_pen_size_strokes_segments() {
+    let code = r#"PENDOWN
+FORWARD "10
+SETPENSIZE "4
+MAKE "size PENSIZE
+FORWARD "10
+SETPENSIZE / :size "8
+FORWARD "10
+"#;
+    compare_engines(code, "pen_size");
+    let program = init_program_code(code, true);
+    compare_vairable_value(&program, "size", Literal::Float(4.0));
+    let widths: Vec<f32> = program
+        .display_list()
+        .segments()
+        .map(|segment| segment.width())
+        .collect();
+    assert_eq!(widths, vec![1.0, 4.0, 0.5]);
+    assert_eq!(
+        ImageWriter::Auto.resolve(program.display_list()),
+        ImageWriter::Native
+    );
+    let svg = SvgWriter::new(2).write(program.display_list());
+    assert!(svg.contains(r#"stroke-width="0.5" stroke-linecap="round" stroke-linejoin="round">"#));
+
+    let plain = init_program_code("PENDOWN\nFORWARD \"10\n", true);
+    assert_eq!(
+        ImageWriter::Auto.resolve(plain.display_list()),
+        ImageWriter::Unsvg
+    );
+    assert_eq!(
+        ImageWriter::Native.resolve(plain.display_list()),
+        ImageWriter::Native
+    );
+}
+
+//* ****************************************************************************** //
+//*                                                                                //
+//*                                    Colours                                     //
+//*                                                                                //
+//* ****************************************************************************** //
+
+#[test]
+fn test_rgb_colours_and_palette() {
+    let code = r##"PENDOWN
+SETPENCOLOR "2
+MAKE "index COLOR
+SETPENCOLOR [ "255 + "100 "28 "0 ]
+FORWARD "10
+MAKE "rgb COLOR
+SETPENCOLOR "#00FF80
+FORWARD "10
+SETPALETTE "2 [ "1 "2 "3 ]
+SETPENCOLOR "2
+FORWARD "10
+SETPALETTE "2 "#abcdef
+FORWARD "10
+"##;
+    compare_engines(code, "rgb");
+    let program = init_program_code(code, true);
+    compare_vairable_value(&program, "index", Literal::Int(2));
+    compare_vairable_value(&program, "rgb", Literal::Int(-1));
+    let colors: Vec<Rgb> = program
+        .display_list()
+        .segments()
+        .map(|segment| segment.color())
+        .collect();
+    assert_eq!(
+        colors,
+        vec![
+            Rgb::new(255, 128, 0),
+            Rgb::new(0, 255, 128),
+            Rgb::new(1, 2, 3),
+            Rgb::new(171, 205, 239),
+        ]
+    );
+    assert_eq!(program.heap().turtle().palette()[3], Rgb::from(COLORS[3]));
+    assert_eq!(
+        Formatter::format(&"SETPALETTE \"8 \"#0000ff\n".to_string()),
+        "SETPALETTE \"8 [ \"0 \"0 \"255 ]\n"
+    );
+    let error = catch_quietly(|| {
+        let code = "SETPENCOLOR \"16\n".to_string();
+        Program::parse_with_canvas(&code, Box::new(NullCanvas::new(10, 10))).run();
+    });
+    assert_eq!(
+        error.unwrap_err(),
+        "Error: colour indices go from 0 to 15, got 16"
+    );
+}
+
+//* ****************************************************************************** //
+//*                                                                                //
+//*                                  Arcs and dots                                 //
+//*                                                                                //
+//* ****************************************************************************** //
+
+#[test]
+fn test_arc_circle_and_dot() {
+    let code = r#"CIRCLE "5
+PENDOWN
+SETHEADING "90
+ARC "-90 "10
+CIRCLE "4
+DOT
+PENUP
+FORWARD "10
+"#;
+    compare_engines(code, "arcs");
+    let program = init_program_code(code, true);
+    let turtle = program.heap().turtle();
+    assert_eq!(
+        (turtle.x(), turtle.y(), turtle.direction()),
+        (110.0, 100.0, 90)
+    );
+    let white = Rgb::from(COLORS[7]);
+    assert_eq!(
+        program.display_list().primitives(),
+        &vec![
+            Primitive::Arc(Arc::new((100.0, 100.0), 10.0, 90.0, -90.0, white, 1.0)),
+            Primitive::Arc(Arc::new((100.0, 100.0), 4.0, 90.0, 360.0, white, 1.0)),
+            Primitive::Dot(Dot::new((100.0, 100.0), 1.0, white)),
+        ]
+    );
+    assert_eq!(
+        ImageWriter::Auto.resolve(program.display_list()),
+        ImageWriter::Native
+    );
+    let svg = SvgWriter::new(2).write(program.display_list());
+    assert!(svg.contains(
+        r##"<path d="M110 100A10 10 0 0 0 100 90"/>
+<circle cx="100" cy="100" r="4"/>
+</g>
+<circle cx="100" cy="100" r="0.5" fill="#ffffff"/>
+"##
+    ));
+
+    let chords = canvas::chords(&Arc::new((100.0, 100.0), 10.0, 0.0, 5.0, white, 1.0));
+    assert_eq!(
+        chords.iter().map(Line::direction).collect::<Vec<_>>(),
+        vec![91, 93, 95]
+    );
+    assert_eq!(chords[0].from(), (100.0, 90.0));
+}
+
+//* ****************************************************************************** //
+//*                                                                                //
+//*                                      Fills                                     //
+//*                                                                                //
+//* ****************************************************************************** //
+
+#[test]
+fn test_begin_and_end_fill() {
+    let code = r##"SETFILLCOLOR "#ff8000
+PENDOWN
+BEGINFILL
+FORWARD "10
+TURN "90
+FORWARD "10
+ENDFILL
+PENUP
+SETFILLCOLOR "3
+BEGINFILL
+SETX "90
+ENDFILL
+"##;
+    compare_engines(code, "fills");
+    let program = init_program_code(code, true);
+    assert_eq!(program.heap().turtle().fill_color(), Rgb::from(COLORS[3]));
+    let white = Rgb::from(COLORS[7]);
+    assert_eq!(
+        program.display_list().primitives(),
+        &vec![
+            Primitive::Fill(Fill::new(
+                vec![(100.0, 100.0), (100.0, 90.0), (110.0, 90.0)],
+                Rgb::new(255, 128, 0)
+            )),
+            Primitive::Segment(Segment::new((100.0, 100.0), (100.0, 90.0), white, 1.0)),
+            Primitive::Segment(Segment::new((100.0, 90.0), (110.0, 90.0), white, 1.0)),
+        ]
+    );
+    let svg = SvgWriter::new(2).write(program.display_list());
+    assert!(svg.contains(
+        r##"<polygon points="100,100 100,90 110,90" fill="#ff8000"/>
+<g fill="none" stroke="#ffffff""##
+    ));
+
+    let error = catch_quietly(|| {
+        let code = "ENDFILL\n".to_string();
+        Program::parse_with_canvas(&code, Box::new(NullCanvas::new(10, 10))).run();
+    });
+    assert_eq!(error.unwrap_err(), "Error: ENDFILL without BEGINFILL");
+}
+
+//* ****************************************************************************** //
+//*                                                                                //
+//*                                     Labels                                     //
+//*                                                                                //
+//* ****************************************************************************** //
+
+#[test]
+fn test_label_text_and_strokes() {
+    let code = r#"LABEL "Hi!
+SETLABELHEIGHT "18
+TURN "90
+LABEL [a  <b>   &c]
+"#;
+    compare_engines(code, "labels");
+    assert_eq!(
+        Formatter::format(&code.to_string()),
+        "LABEL \"Hi!\nSETLABELHEIGHT \"18\nTURN \"90\nLABEL [a <b> &c]\n"
+    );
+    let program = init_program_code(code, true);
+    let white = Rgb::from(COLORS[7]);
+    assert_eq!(
+        program.display_list().primitives(),
+        &vec![
+            Primitive::Label(Label::new(
+                (100.0, 100.0),
+                0.0,
+                12.0,
+                "Hi!".to_string(),
+                white
+            )),
+            Primitive::Label(Label::new(
+                (100.0, 100.0),
+                90.0,
+                18.0,
+                "a <b> &c".to_string(),
+                white
+            )),
+        ]
+    );
+    let svg = SvgWriter::new(2).write(program.display_list());
+    assert!(svg.contains(
+        r##"<text x="100" y="100" font-family="sans-serif" font-size="12" fill="#ffffff">Hi!</text>
+<text x="100" y="100" font-family="sans-serif" font-size="18" fill="#ffffff" transform="rotate(90 100 100)">a &lt;b&gt; &amp;c</text>
+"##
+    ));
+
+    // A dash 9 units tall is a line 4 wide, 3 above the baseline.
+    let dash = Label::new((10.0, 10.0), 0.0, 9.0, "-".to_string(), white);
+    let lines = canvas::letters(&dash);
+    assert_eq!(lines.len(), 1);
+    assert_eq!(
+        (lines[0].from(), lines[0].direction(), lines[0].length()),
+        ((10.0, 7.0), 90, 4.0)
+    );
+
+    let error = catch_quietly(|| {
+        let code = "LABEL \"two words\n".to_string();
+        Program::parse_with_canvas(&code, Box::new(NullCanvas::new(10, 10)));
+    });
+    assert_eq!(
+        error.unwrap_err(),
+        "Error: LABEL takes a \"word or a [list of words]"
+    );
+}
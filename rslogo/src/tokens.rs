@@ -1,13 +1,17 @@
 use std::{
     collections::VecDeque,
-    mem::swap,
+    mem::{swap, take},
     ops::{Add, Div, Mul, Sub},
 };
 
-use crate::{ast::Statement, parsers::ParserFactory};
-use serde::Serialize;
+use crate::{
+    ast::{Span, Statement},
+    panics::catch_quietly,
+    parsers::ParserFactory,
+};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ValueType {
     Float(f32),
     Int(i32),
@@ -139,7 +143,7 @@ impl PartialOrd for ValueType {
     }
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum IdentifierType {
     Variable(String),
     Procedure(String),
@@ -163,13 +167,13 @@ impl IdentifierType {
     }
 }
 
-#[derive(Clone, Serialize, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
 pub enum AssignmentType {
     Make,
     Addassign,
 }
 
-#[derive(Clone, Eq, Hash, PartialEq, Serialize, Debug)]
+#[derive(Clone, Eq, Hash, PartialEq, Serialize, Deserialize, Debug)]
 pub enum CommandType {
     Penup,
     Pendown,
@@ -182,14 +186,105 @@ pub enum CommandType {
     Setheading,
     Setx,
     Sety,
+    Setpensize,
+    Setpalette,
+    Arc,
+    Circle,
+    Dot,
+    Beginfill,
+    Endfill,
+    Setfillcolor,
+    Label,
+    Setlabelheight,
+}
+
+impl CommandType {
+    pub const ALL: [CommandType; 21] = [
+        CommandType::Penup,
+        CommandType::Pendown,
+        CommandType::Forward,
+        CommandType::Back,
+        CommandType::Left,
+        CommandType::Right,
+        CommandType::Setpencolor,
+        CommandType::Turn,
+        CommandType::Setheading,
+        CommandType::Setx,
+        CommandType::Sety,
+        CommandType::Setpensize,
+        CommandType::Setpalette,
+        CommandType::Arc,
+        CommandType::Circle,
+        CommandType::Dot,
+        CommandType::Beginfill,
+        CommandType::Endfill,
+        CommandType::Setfillcolor,
+        CommandType::Label,
+        CommandType::Setlabelheight,
+    ];
+    /// The Logo spelling `Tokenizer` recognises.
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            CommandType::Penup => "PENUP",
+            CommandType::Pendown => "PENDOWN",
+            CommandType::Forward => "FORWARD",
+            CommandType::Back => "BACK",
+            CommandType::Left => "LEFT",
+            CommandType::Right => "RIGHT",
+            CommandType::Setpencolor => "SETPENCOLOR",
+            CommandType::Turn => "TURN",
+            CommandType::Setheading => "SETHEADING",
+            CommandType::Setx => "SETX",
+            CommandType::Sety => "SETY",
+            CommandType::Setpensize => "SETPENSIZE",
+            CommandType::Setpalette => "SETPALETTE",
+            CommandType::Arc => "ARC",
+            CommandType::Circle => "CIRCLE",
+            CommandType::Dot => "DOT",
+            CommandType::Beginfill => "BEGINFILL",
+            CommandType::Endfill => "ENDFILL",
+            CommandType::Setfillcolor => "SETFILLCOLOR",
+            CommandType::Label => "LABEL",
+            CommandType::Setlabelheight => "SETLABELHEIGHT",
+        }
+    }
+    /// Where the `[r g b]` channels start among the arguments of a colour
+    /// command that was given them, e.g. 1 for `SETPALETTE "8 [ "0 "0 "0 ]`.
+    pub fn rgb_start(&self, argc: usize) -> Option<usize> {
+        match (self, argc) {
+            (CommandType::Setpencolor, 3) => Some(0),
+            (CommandType::Setfillcolor, 3) => Some(0),
+            (CommandType::Setpalette, 4) => Some(1),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Clone, Eq, Hash, PartialEq, Serialize, Debug)]
+#[derive(Clone, Eq, Hash, PartialEq, Serialize, Deserialize, Debug)]
 pub enum QueryType {
     Xcor,
     Ycor,
     Heading,
     Color,
+    Pensize,
+}
+impl QueryType {
+    pub const ALL: [QueryType; 5] = [
+        QueryType::Xcor,
+        QueryType::Ycor,
+        QueryType::Heading,
+        QueryType::Color,
+        QueryType::Pensize,
+    ];
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            QueryType::Xcor => "XCOR",
+            QueryType::Ycor => "YCOR",
+            QueryType::Heading => "HEADING",
+            QueryType::Color => "COLOR",
+            QueryType::Pensize => "PENSIZE",
+        }
+    }
 }
 #[derive(Clone, Debug, Serialize)]
 pub enum KeywordType {
@@ -199,7 +294,7 @@ pub enum KeywordType {
     End,
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum LogicalOperation {
     Eq,  // == / Eq
     Ne,  // != / Ne
@@ -209,7 +304,7 @@ pub enum LogicalOperation {
     Or,  // || / Or
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum CalculationOperation {
     Plus,  // +
     Dash,  // -
@@ -222,8 +317,11 @@ pub enum TokenType {
     Bracketleft,  // [
     Bracketright, // ]
     Space,
+    Line(usize), // start of a source line, 1-based
     // Variable(String),
     Value(ValueType),
+    /// The text of a `LABEL`, from `"word` or `[some words]`.
+    Word(String),
     Identifier(IdentifierType),
     Command(CommandType),
     Query(QueryType),
@@ -268,6 +366,12 @@ impl TokenType {
     pub fn is_space(&self) -> bool {
         matches!(self, TokenType::Space)
     }
+    pub fn expect_line(&self) -> Option<usize> {
+        match self {
+            TokenType::Line(line) => Some(*line),
+            _ => None,
+        }
+    }
 
     pub fn is_binary(&self) -> bool {
         matches!(self, TokenType::Calculation(_) | TokenType::Logic(_))
@@ -276,31 +380,51 @@ impl TokenType {
 
 pub struct Tokenizer<'a> {
     tokens: VecDeque<TokenType>,
+    comments: Vec<(usize, String)>,
+    literals: Vec<String>,
     logo: &'a String,
 }
 
 impl Tokenizer<'_> {
-    pub fn new(logo: &String) -> Tokenizer {
+    pub fn new(logo: &String) -> Tokenizer<'_> {
         Tokenizer {
             tokens: VecDeque::new(),
+            comments: Vec::new(),
+            literals: Vec::new(),
             logo,
         }
     }
 
     pub fn scan(&mut self) {
-        for line in self.logo.lines() {
+        for (index, line) in self.logo.lines().enumerate() {
             if line.trim().starts_with("//") {
+                self.comments.push((index + 1, line.trim().to_string()));
                 continue;
             }
             if line.trim().is_empty() {
                 continue;
             }
-            line.trim().split_ascii_whitespace().for_each(|token| {
-                self.scan_token(token);
-            });
+            self.tokens.push_back(TokenType::Line(index + 1));
+            if let Some(text) = Tokenizer::scan_label(line.trim()) {
+                self.tokens
+                    .push_back(TokenType::Command(CommandType::Label));
+                self.tokens.push_back(TokenType::Word(text));
+            } else {
+                line.trim().split_ascii_whitespace().for_each(|token| {
+                    self.scan_token(token);
+                });
+            }
             self.tokens.push_back(TokenType::Space)
         }
     }
+    /// Comment lines skipped by `scan`, with their 1-based line numbers.
+    pub fn move_comments(&mut self) -> Vec<(usize, String)> {
+        take(&mut self.comments)
+    }
+    /// Literal tokens as they were written, in source order.
+    pub fn move_literals(&mut self) -> Vec<String> {
+        take(&mut self.literals)
+    }
     pub fn move_token(&mut self) -> VecDeque<TokenType> {
         let mut tokens: VecDeque<TokenType> = VecDeque::new();
         swap(&mut self.tokens, &mut tokens);
@@ -308,19 +432,41 @@ impl Tokenizer<'_> {
     }
 
     fn scan_token(&mut self, token: &str) {
+        let token_type = Tokenizer::classify(token);
+        if matches!(token_type, TokenType::Value(_)) {
+            self.literals.push(token.to_string());
+        }
+        self.tokens.push_back(token_type)
+    }
+
+    /// The token a single whitespace-separated word scans to.
+    pub fn classify(token: &str) -> TokenType {
         if let Some(keywords) = Tokenizer::scan_keywords(token) {
-            self.tokens.push_back(keywords)
+            keywords
         } else if let Some(variable) = Tokenizer::scan_variable(token) {
-            self.tokens.push_back(variable)
+            variable
         } else if let Some(value) = Tokenizer::scan_value(token) {
-            self.tokens.push_back(value)
+            value
         } else if let Some(char) = Tokenizer::scan_char(token) {
-            self.tokens.push_back(char)
+            char
         } else {
-            self.tokens
-                .push_back(TokenType::Identifier(IdentifierType::Procedure(
-                    token.to_string(),
-                )))
+            TokenType::Identifier(IdentifierType::Procedure(token.to_string()))
+        }
+    }
+
+    /// The text of a `LABEL "word` or `LABEL [some words]` line. Label text
+    /// is kept as written, so it is scanned before the line is split.
+    pub fn scan_label(line: &str) -> Option<String> {
+        let argument = line.strip_prefix("LABEL")?;
+        if !argument.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let argument = argument.trim();
+        if let Some(word) = argument.strip_prefix('"') {
+            (!word.contains(char::is_whitespace)).then(|| word.to_string())
+        } else {
+            let words = argument.strip_prefix('[')?.strip_suffix(']')?;
+            Some(words.split_whitespace().collect::<Vec<_>>().join(" "))
         }
     }
 
@@ -337,12 +483,23 @@ impl Tokenizer<'_> {
             "SETHEADING" => Some(TokenType::Command(CommandType::Setheading)),
             "SETX" => Some(TokenType::Command(CommandType::Setx)),
             "SETY" => Some(TokenType::Command(CommandType::Sety)),
+            "SETPENSIZE" => Some(TokenType::Command(CommandType::Setpensize)),
+            "SETPALETTE" => Some(TokenType::Command(CommandType::Setpalette)),
+            "ARC" => Some(TokenType::Command(CommandType::Arc)),
+            "CIRCLE" => Some(TokenType::Command(CommandType::Circle)),
+            "DOT" => Some(TokenType::Command(CommandType::Dot)),
+            "BEGINFILL" => Some(TokenType::Command(CommandType::Beginfill)),
+            "ENDFILL" => Some(TokenType::Command(CommandType::Endfill)),
+            "SETFILLCOLOR" => Some(TokenType::Command(CommandType::Setfillcolor)),
+            "LABEL" => Some(TokenType::Command(CommandType::Label)),
+            "SETLABELHEIGHT" => Some(TokenType::Command(CommandType::Setlabelheight)),
             "MAKE" => Some(TokenType::Assignment(AssignmentType::Make)),
             "ADDASSIGN" => Some(TokenType::Assignment(AssignmentType::Addassign)),
             "XCOR" => Some(TokenType::Query(QueryType::Xcor)),
             "YCOR" => Some(TokenType::Query(QueryType::Ycor)),
             "HEADING" => Some(TokenType::Query(QueryType::Heading)),
             "COLOR" => Some(TokenType::Query(QueryType::Color)),
+            "PENSIZE" => Some(TokenType::Query(QueryType::Pensize)),
             "IF" => Some(TokenType::Keyword(KeywordType::If)),
             "WHILE" => Some(TokenType::Keyword(KeywordType::While)),
             "TO" => Some(TokenType::Keyword(KeywordType::To)),
@@ -413,6 +570,7 @@ pub trait StatementParser {
 }
 pub struct ASTParser {
     tokens: VecDeque<TokenType>,
+    line: usize,
 }
 
 impl ASTParser {
@@ -421,10 +579,11 @@ impl ASTParser {
         tokenizer.scan();
         ASTParser {
             tokens: tokenizer.move_token(),
+            line: 0,
         }
     }
     pub fn new_from_tokens(tokens: VecDeque<TokenType>) -> ASTParser {
-        ASTParser { tokens }
+        ASTParser { tokens, line: 0 }
     }
 
     pub fn parse_from_tokens(tokens: &[TokenType]) -> Vec<Statement> {
@@ -434,7 +593,46 @@ impl ASTParser {
 
     pub fn parse_statement(&mut self) -> Vec<Statement> {
         let mut statements: Vec<Statement> = Vec::new();
+        while let Some((span, buf)) = self.next_statement() {
+            let mut statement = ParserFactory::create(&buf).parse(&buf);
+            statement.set_span(span);
+            statements.push(statement);
+        }
+        statements
+    }
+
+    /// Parses every statement it can, turning each panic into a `SyntaxError`
+    /// and carrying on with the next statement.
+    pub fn parse_recovering(&mut self) -> (Vec<Statement>, Vec<SyntaxError>) {
+        let mut statements: Vec<Statement> = Vec::new();
+        let mut errors: Vec<SyntaxError> = Vec::new();
+        loop {
+            let start = self.line;
+            let parsed = match catch_quietly(|| self.next_statement()) {
+                Ok(Some((span, buf))) => catch_quietly(|| ParserFactory::create(&buf).parse(&buf))
+                    .map(|mut statement| {
+                        statement.set_span(span);
+                        statement
+                    })
+                    .map_err(|message| (span, message)),
+                Ok(None) => break,
+                Err(message) => Err((Span::new(start.max(self.line), self.line), message)),
+            };
+            match parsed {
+                Ok(statement) => statements.push(statement),
+                Err((span, message)) => errors.push(SyntaxError { span, message }),
+            }
+        }
+        (statements, errors)
+    }
+
+    /// Collects the tokens of the next top-level statement with the lines it covers.
+    fn next_statement(&mut self) -> Option<(Span, Vec<TokenType>)> {
         while let Some(token) = self.tokens.pop_front() {
+            if let Some(start) = token.expect_line() {
+                self.line = start;
+                continue;
+            }
             let mut buf: Vec<TokenType> = Vec::new();
             match &token {
                 TokenType::Keyword(keyword) => {
@@ -491,8 +689,29 @@ impl ASTParser {
                     }
                 }
             };
-            statements.push(ParserFactory::create(&buf).parse(&buf));
+            let end = buf
+                .iter()
+                .rev()
+                .find_map(|token| token.expect_line())
+                .unwrap_or(self.line);
+            return Some((Span::new(self.line, end), buf));
         }
-        statements
+        None
+    }
+}
+
+/// A statement the parser rejected, with the lines it spans.
+#[derive(Clone, Debug)]
+pub struct SyntaxError {
+    span: Span,
+    message: String,
+}
+
+impl SyntaxError {
+    pub fn span(&self) -> Span {
+        self.span
+    }
+    pub fn message(&self) -> &String {
+        &self.message
     }
 }
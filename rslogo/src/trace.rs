@@ -0,0 +1,81 @@
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::{ast::Callee, commands::Literal, heap::Heap, hooks::ExecutionHook, turtle::Turtle};
+
+#[derive(Clone, Copy, Serialize)]
+pub struct TurtleState {
+    x: f32,
+    y: f32,
+    heading: i32,
+    pen: bool,
+    color: i32,
+}
+
+impl From<&Turtle> for TurtleState {
+    fn from(turtle: &Turtle) -> TurtleState {
+        TurtleState {
+            x: turtle.x(),
+            y: turtle.y(),
+            heading: turtle.direction(),
+            pen: turtle.pen(),
+            color: turtle.color(),
+        }
+    }
+}
+
+/// One executed command or query.
+#[derive(Serialize)]
+pub struct TraceRecord<'a> {
+    line: usize,
+    kind: &'static str,
+    callee: &'a Callee,
+    arguments: &'a [Literal],
+    before: TurtleState,
+    after: TurtleState,
+}
+
+/// Writes a JSON Lines record for every built-in the interpreter executes.
+/// Records are flushed as they are written, because an error exits the
+/// process without unwinding and the trace leading up to it is what matters.
+pub struct Tracer<W: Write> {
+    output: W,
+    pending: Option<(Vec<Literal>, TurtleState)>,
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(output: W) -> Tracer<W> {
+        Tracer {
+            output,
+            pending: None,
+        }
+    }
+}
+
+impl<W: Write> ExecutionHook for Tracer<W> {
+    fn before_call(&mut self, _callee: &Callee, arguments: &[Literal], heap: &Heap) {
+        self.pending = Some((arguments.to_vec(), TurtleState::from(heap.turtle())));
+    }
+
+    fn after_call(&mut self, callee: &Callee, heap: &Heap) {
+        let Some((arguments, before)) = self.pending.take() else {
+            return;
+        };
+        let record = TraceRecord {
+            line: heap.line(),
+            kind: match callee {
+                Callee::Command(_) => "Command",
+                Callee::Query(_) => "Query",
+                Callee::Procedure(_) => "Procedure",
+            },
+            callee,
+            arguments: &arguments,
+            before,
+            after: TurtleState::from(heap.turtle()),
+        };
+        serde_json::to_writer(&mut self.output, &record).expect("Failed to write trace");
+        writeln!(self.output).expect("Failed to write trace");
+        self.output.flush().expect("Failed to write trace");
+    }
+}
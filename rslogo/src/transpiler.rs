@@ -1,178 +1,94 @@
+mod javascript;
+mod python;
+mod rust;
+mod source_map;
+mod ucblogo;
+
 use std::fmt::Display;
 
+use clap::ValueEnum;
+
 use crate::{
-    ast::{
-        AssignmentExpression, BinaryExpression, BinaryOperation, CallExpression, Callee,
-        IfStatement, ProcedureDeclaration, Statement, WhileStatement,
-    },
-    commands::Value,
-    tokens::{
-        AssignmentType, CalculationOperation, CommandType, LogicalOperation, QueryType, ValueType,
-    },
+    ast::{ProcedureDeclaration, Statement},
+    tokens::ValueType,
 };
 
-pub trait Translater {
-    fn to_python(&self, _num_tab: usize) -> String;
-}
+use self::{
+    javascript::JavaScriptBackend, python::PythonBackend, rust::RustBackend,
+    ucblogo::UcbLogoBackend,
+};
 
-impl Translater for Statement {
-    fn to_python(&self, _num_tab: usize) -> String {
-        match self {
-            Statement::CallExpression(call) => call.to_python(_num_tab),
-            Statement::AssignmentExpression(assign) => assign.to_python(_num_tab),
-            Statement::If(if_statement) => if_statement.to_python(_num_tab),
-            Statement::While(while_statement) => while_statement.to_python(_num_tab),
-            Statement::ProcedureDeclaration(procedure_declaration) => {
-                procedure_declaration.to_python(_num_tab)
-            }
-        }
-    }
-}
+pub use self::source_map::{SourceMap, SourceMapFormat};
 
-impl Translater for CallExpression {
-    fn to_python(&self, _num_tab: usize) -> String {
-        let mut buf = String::new();
-        buf.push_str(&format!("{}{}(", "    ".repeat(_num_tab), self.callee()));
-        let mut vec_list = Vec::new();
-        for value in self.arguments() {
-            vec_list.push(format!("{}", value));
-        }
-        buf.push_str(&format!("{})\n", vec_list.join(", ")));
-        buf
-    }
-}
-impl Translater for IfStatement {
-    fn to_python(&self, _num_tab: usize) -> String {
-        let mut buf = String::new();
-        buf.push_str(&format!("{}if {}:\n", "    ".repeat(_num_tab), self.test()));
-        let mut vec_list = Vec::new();
-        for statement in self.consequent() {
-            vec_list.push(statement.to_python(_num_tab + 1));
-        }
-        buf.push_str(&format!("{}\n", vec_list.join("")));
-        buf
-    }
-}
-impl Translater for WhileStatement {
-    fn to_python(&self, _num_tab: usize) -> String {
-        let mut buf = String::new();
-        buf.push_str(&format!(
-            "{}while {}:\n",
-            "    ".repeat(_num_tab),
-            self.test()
-        ));
-        let mut vec_list = Vec::new();
-        for statement in self.body() {
-            vec_list.push(statement.to_python(_num_tab + 1));
-        }
-        buf.push_str(&format!("{}\n", vec_list.join("")));
-        buf
-    }
+/// Turns a parsed program into source code for another language, recording
+/// in `map` which statement each generated line came from.
+pub trait Backend {
+    fn emit(
+        &self,
+        statements: &[Statement],
+        width: u32,
+        height: u32,
+        map: &mut SourceMap,
+    ) -> String;
 }
 
-impl Translater for BinaryExpression {
-    fn to_python(&self, _num_tab: usize) -> String {
-        let mut buf = String::new();
-        let left = self.left();
-        let right = self.right();
-        buf.push_str(&format!("({} {} {})", left, self.operation(), right));
-        buf
-    }
-}
-impl Translater for ProcedureDeclaration {
-    fn to_python(&self, _num_tab: usize) -> String {
-        let mut buf = String::new();
-        buf.push_str(&format!("def {}(", self.name()));
-        let mut vec_list = Vec::new();
-        for value in self.arguments() {
-            vec_list.push(value.to_string());
-        }
-        buf.push_str(&format!("{}):\n", vec_list.join(", ")));
-        for statement in self.body() {
-            buf.push_str(&statement.to_python(_num_tab + 1).to_string());
-        }
-        buf.push('\n');
-        buf
-    }
-}
-impl Translater for AssignmentExpression {
-    fn to_python(&self, _num_tab: usize) -> String {
-        let mut buf = String::new();
-        match self.operation() {
-            AssignmentType::Make => buf.push_str(&format!(
-                "{}{} {} {}\n",
-                "    ".repeat(_num_tab),
-                self.left(),
-                self.operation(),
-                self.right()
-            )),
-            AssignmentType::Addassign => buf.push_str(&format!(
-                "{}{} {} {}\n",
-                "    ".repeat(_num_tab),
-                self.left(),
-                self.operation(),
-                self.right()
-            )),
-        };
-        buf
-    }
-}
-impl Display for AssignmentType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            AssignmentType::Make => write!(f, "="),
-            AssignmentType::Addassign => write!(f, "+="),
-        }
-    }
-}
-impl Display for CommandType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            CommandType::Penup => write!(f, "penup"),
-            CommandType::Forward => write!(f, "forward"),
-            CommandType::Pendown => write!(f, "pendown"),
-            CommandType::Back => write!(f, "back"),
-            CommandType::Left => write!(f, "left"),
-            CommandType::Right => write!(f, "right"),
-            CommandType::Setheading => write!(f, "setheading"),
-            CommandType::Setpencolor => write!(f, "setpencolor"),
-            CommandType::Turn => write!(f, "turn"),
-            CommandType::Setx => write!(f, "setx"),
-            CommandType::Sety => write!(f, "sety"),
-        }
-    }
-}
-impl Display for QueryType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            QueryType::Xcor => write!(f, "xcor"),
-            QueryType::Ycor => write!(f, "ycor"),
-            QueryType::Heading => write!(f, "heading"),
-            QueryType::Color => write!(f, "pendown"),
-        }
-    }
+/// The languages `--emit` can produce.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Target {
+    /// Python using the turtle module
+    #[default]
+    Python,
+    /// An HTML page that draws on a canvas
+    Js,
+    /// A Rust program that draws with unsvg
+    Rust,
+    /// Standard Logo for UCBLogo and FMSLogo
+    #[value(name = "ucblogo")]
+    UcbLogo,
 }
 
-impl Display for Callee {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Callee::Command(command) => write!(f, "{}", command),
-            Callee::Query(query) => write!(f, "{}", query),
-            Callee::Procedure(procedure) => write!(f, "{}", procedure),
+pub struct BackendFactory {}
+
+impl BackendFactory {
+    pub fn build(target: Target) -> Box<dyn Backend> {
+        match target {
+            Target::Python => Box::new(PythonBackend {}),
+            Target::Js => Box::new(JavaScriptBackend {}),
+            Target::Rust => Box::new(RustBackend {}),
+            Target::UcbLogo => Box::new(UcbLogoBackend {}),
         }
     }
 }
 
-impl Display for Value {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Value::Literal(literal) => write!(f, "{}", literal),
-            Value::Identifier(variable) => write!(f, "{}", variable.expect_variable().unwrap()),
-            Value::Statement(stament) => write!(f, "{}", stament.to_python(0)),
-            Value::BinaryExpression(binary) => write!(f, "{}", binary.to_python(0)),
+/// Every procedure declared in a block, including nested declarations, for
+/// backends that can only define procedures at the top level. When a name is
+/// declared twice, the first declaration is kept.
+pub fn procedure_declarations<'a>(
+    statements: &'a [Statement],
+    declarations: &mut Vec<&'a ProcedureDeclaration>,
+) {
+    for statement in statements {
+        match statement {
+            Statement::ProcedureDeclaration(procedure) => {
+                if !declarations
+                    .iter()
+                    .any(|declared| declared.name() == procedure.name())
+                {
+                    declarations.push(procedure);
+                }
+                procedure_declarations(procedure.body(), declarations);
+            }
+            Statement::If(if_statement) => {
+                procedure_declarations(if_statement.consequent(), declarations)
+            }
+            Statement::While(while_statement) => {
+                procedure_declarations(while_statement.body(), declarations)
+            }
+            Statement::CallExpression(_) | Statement::AssignmentExpression(_) => {}
         }
     }
 }
+
 impl Display for ValueType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -182,23 +98,3 @@ impl Display for ValueType {
         }
     }
 }
-impl Display for BinaryOperation {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            BinaryOperation::Calculation(cal) => match cal {
-                CalculationOperation::Plus => write!(f, "+"),
-                CalculationOperation::Dash => write!(f, "-"),
-                CalculationOperation::Star => write!(f, "*"),
-                CalculationOperation::Slash => write!(f, "/"),
-            },
-            BinaryOperation::Logic(logic) => match logic {
-                LogicalOperation::And => write!(f, "&&"),
-                LogicalOperation::Or => write!(f, "||"),
-                LogicalOperation::Eq => write!(f, "=="),
-                LogicalOperation::Ne => write!(f, "!="),
-                LogicalOperation::Lt => write!(f, "<"),
-                LogicalOperation::Gt => write!(f, ">"),
-            },
-        }
-    }
-}
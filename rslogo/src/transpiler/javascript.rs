@@ -0,0 +1,430 @@
+use unsvg::COLORS;
+
+use crate::{
+    ast::{BinaryOperation, CallExpression, Callee, Statement},
+    commands::Value,
+    tokens::{
+        AssignmentType, CalculationOperation, CommandType, IdentifierType, LogicalOperation,
+        QueryType, ValueType,
+    },
+};
+
+use super::{Backend, SourceMap};
+
+const INDENT: &str = "    ";
+
+/// The turtle follows `Turtle::move_`: the origin is the top-left corner, y
+/// grows down, heading 0 points up and turns clockwise, and every end point is
+/// rounded to 1/256 of a pixel as `unsvg` does. Logo variables live in `vars`
+/// so procedure parameters can shadow them and be restored on return.
+const JS_RUNTIME: &str = r##"const canvas = document.getElementById("canvas");
+const context = canvas.getContext("2d");
+context.fillStyle = COLORS[0];
+context.fillRect(0, 0, WIDTH, HEIGHT);
+context.lineWidth = 1;
+context.lineCap = "round";
+context.lineJoin = "round";
+
+const vars = {};
+const procedures = {};
+
+function quantize(value) {
+    return Math.sign(value) * Math.round(Math.abs(value) * 256) / 256;
+}
+
+function paletteIndex(color) {
+    const index = Math.trunc(color);
+    if (!(index >= 0 && index < 16)) {
+        throw new Error(`Error: colour indices go from 0 to 15, got ${color}`);
+    }
+    return index;
+}
+
+function rgb(...channels) {
+    return "#" + channels.map((channel) => {
+        const value = Math.trunc(channel);
+        if (!(value >= 0 && value <= 255)) {
+            throw new Error(`Error: colour channels go from 0 to 255, got ${channel}`);
+        }
+        return value.toString(16).padStart(2, "0");
+    }).join("");
+}
+
+function div(left, right) {
+    const quotient = left / right;
+    return Number.isInteger(left) && Number.isInteger(right) ? Math.trunc(quotient) : quotient;
+}
+
+function bind(names, values) {
+    const saved = names.map((name) => [name, vars[name]]);
+    names.forEach((name, index) => {
+        vars[name] = values[index];
+    });
+    return saved;
+}
+
+function restore(saved) {
+    for (const [name, value] of saved) {
+        if (value !== undefined) {
+            vars[name] = value;
+        }
+    }
+}
+
+const turtle = {
+    x: WIDTH / 2,
+    y: HEIGHT / 2,
+    heading: 0,
+    color: 7,
+    stroke: COLORS[7],
+    size: 1,
+    pen: false,
+    fillStyle: COLORS[7],
+    filling: null,
+    labelHeight: 12,
+    move(distance, offset) {
+        const angle = (this.heading + offset - 90) * Math.PI / 180;
+        const x = quantize(this.x + Math.cos(angle) * distance);
+        const y = quantize(this.y + Math.sin(angle) * distance);
+        if (this.pen) {
+            const [x0, y0, style, width] = [this.x, this.y, this.stroke, this.size];
+            this.draw(() => {
+                context.strokeStyle = style;
+                context.lineWidth = width;
+                context.beginPath();
+                context.moveTo(x0, y0);
+                context.lineTo(x, y);
+                context.stroke();
+            });
+        }
+        this.x = x;
+        this.y = y;
+        this.visit();
+    },
+    // Paints now, and again after a fill so outlines stay on top.
+    draw(paint) {
+        paint();
+        if (this.filling) {
+            this.filling.strokes.push(paint);
+        }
+    },
+    visit() {
+        if (this.filling) {
+            this.filling.points.push([this.x, this.y]);
+        }
+    },
+    penup() { this.pen = false; },
+    pendown() { this.pen = true; },
+    forward(distance) { this.move(distance, 0); },
+    back(distance) { this.move(distance, 180); },
+    left(distance) { this.move(distance, 270); },
+    right(distance) { this.move(distance, 90); },
+    setpencolor(...color) {
+        if (color.length === 3) {
+            this.color = -1;
+            this.stroke = rgb(...color);
+        } else {
+            this.color = paletteIndex(color[0]);
+            this.stroke = COLORS[this.color];
+        }
+    },
+    setpalette(color, red, green, blue) {
+        COLORS[paletteIndex(color)] = rgb(red, green, blue);
+        if (this.color === Math.trunc(color)) {
+            this.stroke = COLORS[this.color];
+        }
+    },
+    turn(degrees) { this.heading += Math.trunc(degrees); },
+    setheading(degrees) { this.heading = Math.trunc(degrees); },
+    setx(x) { this.x = x; this.visit(); },
+    sety(y) { this.y = y; this.visit(); },
+    setpensize(size) { this.size = size; },
+    arc(angle, radius) {
+        if (!this.pen) {
+            return;
+        }
+        const start = (this.heading - 90) * Math.PI / 180 + (radius < 0 ? Math.PI : 0);
+        const sweep = Math.max(-360, Math.min(360, angle)) * Math.PI / 180;
+        const [x, y, style, width] = [this.x, this.y, this.stroke, this.size];
+        this.draw(() => {
+            context.strokeStyle = style;
+            context.lineWidth = width;
+            context.beginPath();
+            context.arc(x, y, Math.abs(radius), start, start + sweep, sweep < 0);
+            context.stroke();
+        });
+    },
+    circle(radius) { this.arc(360, radius); },
+    dot() {
+        if (!this.pen) {
+            return;
+        }
+        const [x, y, style, size] = [this.x, this.y, this.stroke, this.size];
+        this.draw(() => {
+            context.fillStyle = style;
+            context.beginPath();
+            context.arc(x, y, size / 2, 0, 2 * Math.PI);
+            context.fill();
+        });
+    },
+    label(text) {
+        const [x, y, heading, style, height] = [this.x, this.y, this.heading, this.stroke, this.labelHeight];
+        this.draw(() => {
+            context.save();
+            context.translate(x, y);
+            context.rotate(heading * Math.PI / 180);
+            context.fillStyle = style;
+            context.font = `${height}px sans-serif`;
+            context.fillText(text, 0, 0);
+            context.restore();
+        });
+    },
+    setlabelheight(height) { this.labelHeight = height; },
+    setfillcolor(...color) {
+        this.fillStyle = color.length === 3 ? rgb(...color) : COLORS[paletteIndex(color[0])];
+    },
+    beginfill() {
+        this.filling = { points: [[this.x, this.y]], strokes: [] };
+    },
+    endfill() {
+        if (!this.filling) {
+            throw new Error("Error: ENDFILL without BEGINFILL");
+        }
+        const { points, strokes } = this.filling;
+        this.filling = null;
+        if (points.length < 3) {
+            return;
+        }
+        context.fillStyle = this.fillStyle;
+        context.beginPath();
+        context.moveTo(...points[0]);
+        for (const [x, y] of points.slice(1)) {
+            context.lineTo(x, y);
+        }
+        context.closePath();
+        context.fill();
+        strokes.forEach((paint) => paint());
+    },
+    xcor() { return this.x; },
+    ycor() { return this.y; },
+    getheading() { return this.heading; },
+    getcolor() { return this.color; },
+    pensize() { return this.size; },
+};
+"##;
+
+/// A standalone HTML page whose script draws the program on a `<canvas>`.
+pub struct JavaScriptBackend {}
+
+impl Backend for JavaScriptBackend {
+    fn emit(
+        &self,
+        statements: &[Statement],
+        width: u32,
+        height: u32,
+        map: &mut SourceMap,
+    ) -> String {
+        let mut buf = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        buf.push_str("<title>rslogo</title>\n</head>\n<body>\n");
+        buf.push_str(&format!(
+            "<canvas id=\"canvas\" width=\"{}\" height=\"{}\"></canvas>\n<script>\n",
+            width, height
+        ));
+        buf.push_str(&format!(
+            "const WIDTH = {};\nconst HEIGHT = {};\n",
+            width, height
+        ));
+        let colors: Vec<String> = COLORS
+            .iter()
+            .map(|color| {
+                format!(
+                    "\"#{:02x}{:02x}{:02x}\"",
+                    color.red, color.green, color.blue
+                )
+            })
+            .collect();
+        buf.push_str(&format!("const COLORS = [{}];\n", colors.join(", ")));
+        buf.push_str(JS_RUNTIME);
+        buf.push('\n');
+        block(statements, 0, &mut buf, map);
+        buf.push_str("</script>\n</body>\n</html>\n");
+        buf
+    }
+}
+
+fn block(statements: &[Statement], depth: usize, buf: &mut String, map: &mut SourceMap) {
+    for statement in statements {
+        let start = buf.len();
+        self::statement(statement, depth, buf, map);
+        map.record(statement.span(), buf, start);
+    }
+}
+
+fn statement(statement: &Statement, depth: usize, buf: &mut String, map: &mut SourceMap) {
+    let indent = INDENT.repeat(depth);
+    match statement {
+        Statement::CallExpression(call) => {
+            buf.push_str(&format!("{}{};\n", indent, call_expression(call)))
+        }
+        Statement::AssignmentExpression(assign) => {
+            let operation = match assign.operation() {
+                AssignmentType::Make => "=",
+                AssignmentType::Addassign => "+=",
+            };
+            buf.push_str(&format!(
+                "{}{} {} {};\n",
+                indent,
+                property("vars", assign.left()),
+                operation,
+                value(assign.right())
+            ));
+        }
+        Statement::If(if_statement) => {
+            buf.push_str(&format!(
+                "{}if ({}) {{\n",
+                indent,
+                condition(if_statement.test())
+            ));
+            block(if_statement.consequent(), depth + 1, buf, map);
+            buf.push_str(&format!("{}}}\n", indent));
+        }
+        Statement::While(while_statement) => {
+            buf.push_str(&format!(
+                "{}while ({}) {{\n",
+                indent,
+                condition(while_statement.test())
+            ));
+            block(while_statement.body(), depth + 1, buf, map);
+            buf.push_str(&format!("{}}}\n", indent));
+        }
+        Statement::ProcedureDeclaration(procedure) => {
+            let names: Vec<String> = procedure
+                .arguments()
+                .iter()
+                .map(|argument| string(argument))
+                .collect();
+            buf.push_str(&format!(
+                "{}{} = function (...args) {{\n",
+                indent,
+                property("procedures", procedure.name())
+            ));
+            buf.push_str(&format!(
+                "{}{}const saved = bind([{}], args);\n",
+                indent,
+                INDENT,
+                names.join(", ")
+            ));
+            block(procedure.body(), depth + 1, buf, map);
+            buf.push_str(&format!("{}{}restore(saved);\n", indent, INDENT));
+            buf.push_str(&format!("{}}};\n", indent));
+        }
+    }
+}
+
+fn call_expression(call: &CallExpression) -> String {
+    let callee = match call.callee() {
+        Callee::Command(command) => format!(
+            "turtle.{}",
+            match command {
+                CommandType::Penup => "penup",
+                CommandType::Pendown => "pendown",
+                CommandType::Forward => "forward",
+                CommandType::Back => "back",
+                CommandType::Left => "left",
+                CommandType::Right => "right",
+                CommandType::Setpencolor => "setpencolor",
+                CommandType::Turn => "turn",
+                CommandType::Setheading => "setheading",
+                CommandType::Setx => "setx",
+                CommandType::Sety => "sety",
+                CommandType::Setpensize => "setpensize",
+                CommandType::Setpalette => "setpalette",
+                CommandType::Arc => "arc",
+                CommandType::Circle => "circle",
+                CommandType::Dot => "dot",
+                CommandType::Beginfill => "beginfill",
+                CommandType::Endfill => "endfill",
+                CommandType::Setfillcolor => "setfillcolor",
+                CommandType::Label => "label",
+                CommandType::Setlabelheight => "setlabelheight",
+            }
+        ),
+        Callee::Query(query) => format!(
+            "turtle.{}",
+            match query {
+                QueryType::Xcor => "xcor",
+                QueryType::Ycor => "ycor",
+                QueryType::Heading => "getheading",
+                QueryType::Color => "getcolor",
+                QueryType::Pensize => "pensize",
+            }
+        ),
+        Callee::Procedure(name) => property("procedures", name),
+    };
+    let arguments: Vec<String> = call.arguments().iter().map(value).collect();
+    format!("{}({})", callee, arguments.join(", "))
+}
+
+fn value(value: &Value) -> String {
+    match value {
+        Value::Literal(ValueType::Int(number)) => number.to_string(),
+        Value::Literal(ValueType::Float(number)) => number.to_string(),
+        Value::Literal(ValueType::Bool(boolean)) => boolean.to_string(),
+        Value::Identifier(IdentifierType::Variable(name)) => property("vars", name),
+        Value::Identifier(IdentifierType::Procedure(name)) => property("procedures", name),
+        Value::Variable(variable) => property("vars", variable.name()),
+        Value::Statement(call) => call_expression(call),
+        Value::Word(word) => serde_json::to_string(word).expect("Strings always serialize"),
+        Value::BinaryExpression(binary) => {
+            let left = self::value(binary.left());
+            let right = self::value(binary.right());
+            let operation = match binary.operation() {
+                BinaryOperation::Calculation(CalculationOperation::Slash) => {
+                    return format!("div({}, {})", left, right);
+                }
+                BinaryOperation::Calculation(calculation) => match calculation {
+                    CalculationOperation::Plus => "+",
+                    CalculationOperation::Dash => "-",
+                    CalculationOperation::Star => "*",
+                    CalculationOperation::Slash => "/",
+                },
+                BinaryOperation::Logic(logic) => match logic {
+                    LogicalOperation::Eq => "===",
+                    LogicalOperation::Ne => "!==",
+                    LogicalOperation::Gt => ">",
+                    LogicalOperation::Lt => "<",
+                    LogicalOperation::And => "&&",
+                    LogicalOperation::Or => "||",
+                },
+            };
+            format!("({} {} {})", left, operation, right)
+        }
+    }
+}
+
+/// A test without the parentheses `if` and `while` already supply.
+fn condition(test: &Value) -> String {
+    let text = value(test);
+    match test {
+        Value::BinaryExpression(_) if text.starts_with('(') => text[1..text.len() - 1].to_string(),
+        _ => text,
+    }
+}
+
+/// `object.name`, or `object["name"]` when the name is not a JS identifier.
+fn property(object: &str, name: &str) -> String {
+    let mut chars = name.chars();
+    let identifier = chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_' || first == '$')
+        && chars.all(|char| char.is_ascii_alphanumeric() || char == '_' || char == '$');
+    if identifier {
+        format!("{}.{}", object, name)
+    } else {
+        format!("{}[{}]", object, string(name))
+    }
+}
+
+fn string(text: &str) -> String {
+    serde_json::to_string(text).expect("Strings always serialize")
+}
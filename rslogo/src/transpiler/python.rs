@@ -0,0 +1,518 @@
+use unsvg::COLORS;
+
+use crate::{
+    ast::{
+        AssignmentExpression, BinaryExpression, BinaryOperation, CallExpression, Callee,
+        ProcedureDeclaration, Statement,
+    },
+    commands::Value,
+    tokens::{
+        AssignmentType, CalculationOperation, CommandType, LogicalOperation, QueryType, ValueType,
+    },
+};
+
+use super::{Backend, SourceMap};
+
+/// Turtle state and the commands Logo has but the `turtle` module spells
+/// differently. Positions are tracked in Logo's coordinates, measured from the
+/// top-left corner with y growing down. Moves are worked out in single
+/// precision and rounded to 1/256 of a pixel the way `unsvg` does, so loops
+/// that compare XCOR or YCOR end as they do in rslogo, though arithmetic in the
+/// program itself is still done in double precision. Logo variables live in
+/// `_vars` so procedure parameters can shadow them and be restored on return.
+/// LEFT and RIGHT strafe without turning, and SETX/SETY move without drawing.
+/// Python's turtle also adds the points of arcs to a fill's outline, and it
+/// writes labels without turning them to the heading.
+const PYTHON_RUNTIME: &str = r##"turtle.setup(WIDTH, HEIGHT)
+turtle.mode("logo")
+turtle.bgcolor("black")
+turtle.tracer(0)
+turtle.hideturtle()
+turtle.penup()
+turtle.pencolor(COLORS[7])
+turtle.fillcolor(COLORS[7])
+_x = WIDTH / 2
+_y = HEIGHT / 2
+_heading = 0
+_color = 7
+_pensize = 1.0
+_labelheight = 12.0
+_vars = {}
+
+
+def _f32(value):
+    return struct.unpack("f", struct.pack("f", value))[0]
+
+
+def _quantize(value):
+    scaled = _f32(value * 256)
+    return _f32(math.copysign(math.floor(abs(scaled) + 0.5), scaled) / 256)
+
+
+def _goto(x, y):
+    global _x, _y
+    _x = x
+    _y = y
+    turtle.goto(_x - WIDTH / 2, HEIGHT / 2 - _y)
+
+
+def _move(distance, offset):
+    angle = _f32(_f32(_heading + offset - 90) * _f32(_f32(math.pi) / 180))
+    distance = _f32(distance)
+    x = _quantize(_x)
+    y = _quantize(_y)
+    _goto(
+        _quantize(_f32(x + _f32(_f32(math.cos(angle)) * distance))),
+        _quantize(_f32(y + _f32(_f32(math.sin(angle)) * distance))),
+    )
+
+
+def _jump(x, y):
+    down = turtle.isdown()
+    turtle.penup()
+    _goto(_f32(x), _f32(y))
+    if down:
+        turtle.pendown()
+
+
+def _bind(names, values):
+    saved = [(name, _vars.get(name)) for name in names]
+    for name, value in zip(names, values):
+        _vars[name] = value
+    return saved
+
+
+def _restore(saved):
+    for name, value in saved:
+        if value is not None:
+            _vars[name] = value
+
+
+def _palette_index(color):
+    color = int(color)
+    if not 0 <= color < 16:
+        raise ValueError(f"Error: colour indices go from 0 to 15, got {color}")
+    return color
+
+
+def _rgb(red, green, blue):
+    channels = tuple(int(channel) for channel in (red, green, blue))
+    for channel in channels:
+        if not 0 <= channel <= 255:
+            raise ValueError(f"Error: colour channels go from 0 to 255, got {channel}")
+    return "#%02x%02x%02x" % channels
+
+
+def _div(left, right):
+    if isinstance(left, int) and isinstance(right, int):
+        return int(left / right)
+    return left / right
+
+
+def penup():
+    turtle.penup()
+
+
+def pendown():
+    turtle.pendown()
+
+
+def forward(distance):
+    _move(distance, 0)
+
+
+def back(distance):
+    _move(distance, 180)
+
+
+def left(distance):
+    _move(distance, 270)
+
+
+def right(distance):
+    _move(distance, 90)
+
+
+def setpencolor(*color):
+    global _color
+    if len(color) == 3:
+        _color = -1
+        turtle.pencolor(_rgb(*color))
+    else:
+        _color = _palette_index(color[0])
+        turtle.pencolor(COLORS[_color])
+
+
+def setpalette(color, red, green, blue):
+    COLORS[_palette_index(color)] = _rgb(red, green, blue)
+    if _color == int(color):
+        turtle.pencolor(COLORS[_color])
+
+
+def turn(degrees):
+    global _heading
+    _heading += degrees
+    turtle.setheading(_heading)
+
+
+def setheading(degrees):
+    global _heading
+    _heading = degrees
+    turtle.setheading(_heading)
+
+
+def setx(x):
+    _jump(x, _y)
+
+
+def sety(y):
+    _jump(_x, y)
+
+
+def setpensize(size):
+    global _pensize
+    _pensize = float(size)
+    turtle.pensize(size)
+
+
+def arc(angle, radius):
+    if not turtle.isdown():
+        return
+    start = math.radians(_heading)
+    turtle.penup()
+    turtle.goto(
+        _x + radius * math.sin(start) - WIDTH / 2,
+        HEIGHT / 2 - (_y - radius * math.cos(start)),
+    )
+    turtle.setheading(_heading + 90)
+    turtle.pendown()
+    turtle.circle(-radius, angle)
+    turtle.penup()
+    turtle.goto(_x - WIDTH / 2, HEIGHT / 2 - _y)
+    turtle.setheading(_heading)
+    turtle.pendown()
+
+
+def circle(radius):
+    arc(360, radius)
+
+
+def dot():
+    if turtle.isdown():
+        turtle.dot(_pensize)
+
+
+def label(text):
+    turtle.write(text, font=("Helvetica", -round(_labelheight), "normal"))
+
+
+def setlabelheight(height):
+    global _labelheight
+    _labelheight = float(height)
+
+
+def setfillcolor(*color):
+    if len(color) == 3:
+        turtle.fillcolor(_rgb(*color))
+    else:
+        turtle.fillcolor(COLORS[_palette_index(color[0])])
+
+
+def beginfill():
+    turtle.begin_fill()
+
+
+def endfill():
+    if not turtle.filling():
+        raise RuntimeError("Error: ENDFILL without BEGINFILL")
+    turtle.end_fill()
+
+
+def xcor():
+    return _x
+
+
+def ycor():
+    return _y
+
+
+def heading():
+    return _heading
+
+
+def color():
+    return _color
+
+
+def pensize():
+    return _pensize
+
+"##;
+
+/// The imports, canvas size, `unsvg` colour palette and turtle helpers that
+/// transpiled statements rely on.
+fn prelude(width: u32, height: u32) -> String {
+    let mut buf = String::from("import math\nimport struct\nimport turtle\n\n");
+    buf.push_str(&format!("WIDTH, HEIGHT = {}, {}\n", width, height));
+    buf.push_str("COLORS = [\n");
+    for color in COLORS.iter() {
+        buf.push_str(&format!(
+            "    \"#{:02x}{:02x}{:02x}\",\n",
+            color.red, color.green, color.blue
+        ));
+    }
+    buf.push_str("]\n");
+    buf.push_str(PYTHON_RUNTIME);
+    buf.push('\n');
+    buf
+}
+
+const EPILOGUE: &str = "\nturtle.update()\nturtle.done()\n";
+
+/// A standalone script for CPython's `turtle` module.
+pub struct PythonBackend {}
+
+impl Backend for PythonBackend {
+    fn emit(
+        &self,
+        statements: &[Statement],
+        width: u32,
+        height: u32,
+        map: &mut SourceMap,
+    ) -> String {
+        let mut buf = prelude(width, height);
+        for statement in statements {
+            let start = buf.len();
+            self::statement(statement, 0, &mut buf, map);
+            map.record(statement.span(), &buf, start);
+        }
+        buf.push_str(EPILOGUE);
+        buf
+    }
+}
+
+trait Translater {
+    fn to_python(&self, _num_tab: usize) -> String;
+}
+
+/// An indented block, with `pass` standing in for an empty one.
+fn block(statements: &[Statement], _num_tab: usize, buf: &mut String, map: &mut SourceMap) {
+    if statements.is_empty() {
+        buf.push_str(&format!("{}pass\n", "    ".repeat(_num_tab)));
+    }
+    for statement in statements {
+        let start = buf.len();
+        self::statement(statement, _num_tab, buf, map);
+        map.record(statement.span(), buf, start);
+    }
+}
+
+fn statement(statement: &Statement, _num_tab: usize, buf: &mut String, map: &mut SourceMap) {
+    match statement {
+        Statement::CallExpression(call) => buf.push_str(&format!(
+            "{}{}\n",
+            "    ".repeat(_num_tab),
+            call.to_python(0)
+        )),
+        Statement::AssignmentExpression(assign) => buf.push_str(&assign.to_python(_num_tab)),
+        Statement::If(if_statement) => {
+            buf.push_str(&format!(
+                "{}if {}:\n",
+                "    ".repeat(_num_tab),
+                if_statement.test().to_python(0)
+            ));
+            block(if_statement.consequent(), _num_tab + 1, buf, map);
+        }
+        Statement::While(while_statement) => {
+            buf.push_str(&format!(
+                "{}while {}:\n",
+                "    ".repeat(_num_tab),
+                while_statement.test().to_python(0)
+            ));
+            block(while_statement.body(), _num_tab + 1, buf, map);
+        }
+        Statement::ProcedureDeclaration(procedure) => {
+            procedure_declaration(procedure, _num_tab, buf, map)
+        }
+    }
+}
+
+/// Parameters are bound in `_vars` on entry and the values they shadowed are
+/// put back on return, as rslogo does.
+fn procedure_declaration(
+    procedure: &ProcedureDeclaration,
+    _num_tab: usize,
+    buf: &mut String,
+    map: &mut SourceMap,
+) {
+    buf.push_str(&format!(
+        "{}def {}(*args):\n",
+        "    ".repeat(_num_tab),
+        procedure_name(procedure.name())
+    ));
+    let mut vec_list = Vec::new();
+    for argument in procedure.arguments() {
+        vec_list.push(string(argument));
+    }
+    buf.push_str(&format!(
+        "{}saved = _bind([{}], args)\n",
+        "    ".repeat(_num_tab + 1),
+        vec_list.join(", ")
+    ));
+    if !procedure.body().is_empty() {
+        block(procedure.body(), _num_tab + 1, buf, map);
+    }
+    buf.push_str(&format!("{}_restore(saved)\n", "    ".repeat(_num_tab + 1)));
+    buf.push('\n');
+}
+
+impl Translater for BinaryExpression {
+    fn to_python(&self, _num_tab: usize) -> String {
+        let mut buf = String::new();
+        let left = self.left().to_python(0);
+        let right = self.right().to_python(0);
+        match self.operation() {
+            BinaryOperation::Calculation(CalculationOperation::Slash) => {
+                buf.push_str(&format!("_div({}, {})", left, right))
+            }
+            op => buf.push_str(&format!("({} {} {})", left, operation(op), right)),
+        }
+        buf
+    }
+}
+impl Translater for AssignmentExpression {
+    fn to_python(&self, _num_tab: usize) -> String {
+        let mut buf = String::new();
+        match self.operation() {
+            AssignmentType::Make => buf.push_str(&format!(
+                "{}{} {} {}\n",
+                "    ".repeat(_num_tab),
+                variable(self.left()),
+                assignment(self.operation()),
+                self.right().to_python(0)
+            )),
+            AssignmentType::Addassign => buf.push_str(&format!(
+                "{}{} {} {}\n",
+                "    ".repeat(_num_tab),
+                variable(self.left()),
+                assignment(self.operation()),
+                self.right().to_python(0)
+            )),
+        };
+        buf
+    }
+}
+impl Translater for CallExpression {
+    fn to_python(&self, _num_tab: usize) -> String {
+        let mut vec_list = Vec::new();
+        for value in self.arguments() {
+            vec_list.push(value.to_python(0));
+        }
+        format!("{}({})", callee(self.callee()), vec_list.join(", "))
+    }
+}
+
+impl Translater for Value {
+    fn to_python(&self, _num_tab: usize) -> String {
+        match self {
+            Value::Literal(ValueType::Bool(true)) => "True".to_string(),
+            Value::Literal(ValueType::Bool(false)) => "False".to_string(),
+            // Keep the decimal point so Python does integer division the same way.
+            Value::Literal(ValueType::Float(float)) => format!("{:?}", float),
+            Value::Literal(literal) => literal.to_string(),
+            Value::Identifier(identifier) => variable(&identifier.expect_variable().unwrap()),
+            Value::Variable(variable) => self::variable(variable.name()),
+            Value::Statement(stament) => stament.to_python(0),
+            Value::BinaryExpression(binary) => binary.to_python(0),
+            Value::Word(word) => string(word),
+        }
+    }
+}
+
+fn string(text: &str) -> String {
+    serde_json::to_string(text).expect("Strings always serialize")
+}
+
+fn variable(name: &str) -> String {
+    format!("_vars[{}]", string(name))
+}
+
+/// Procedures get a `p_` prefix so they cannot clash with the runtime or
+/// Python's builtins. Characters Python does not allow in names are spelled
+/// out as `_x` and their code point, and underscores are doubled, so two
+/// Logo names never end up the same.
+fn procedure_name(name: &str) -> String {
+    let mut buf = String::from("p_");
+    for char in name.chars() {
+        match char {
+            '_' => buf.push_str("__"),
+            char if char.is_ascii_alphanumeric() => buf.push(char),
+            char => buf.push_str(&format!("_x{:x}_", char as u32)),
+        }
+    }
+    buf
+}
+
+fn assignment(operation: &AssignmentType) -> &'static str {
+    match operation {
+        AssignmentType::Make => "=",
+        AssignmentType::Addassign => "+=",
+    }
+}
+
+fn callee(callee: &Callee) -> String {
+    match callee {
+        Callee::Command(command) => match command {
+            CommandType::Penup => "penup",
+            CommandType::Forward => "forward",
+            CommandType::Pendown => "pendown",
+            CommandType::Back => "back",
+            CommandType::Left => "left",
+            CommandType::Right => "right",
+            CommandType::Setheading => "setheading",
+            CommandType::Setpencolor => "setpencolor",
+            CommandType::Turn => "turn",
+            CommandType::Setx => "setx",
+            CommandType::Sety => "sety",
+            CommandType::Setpensize => "setpensize",
+            CommandType::Setpalette => "setpalette",
+            CommandType::Arc => "arc",
+            CommandType::Circle => "circle",
+            CommandType::Dot => "dot",
+            CommandType::Beginfill => "beginfill",
+            CommandType::Endfill => "endfill",
+            CommandType::Setfillcolor => "setfillcolor",
+            CommandType::Label => "label",
+            CommandType::Setlabelheight => "setlabelheight",
+        }
+        .to_string(),
+        Callee::Query(query) => match query {
+            QueryType::Xcor => "xcor",
+            QueryType::Ycor => "ycor",
+            QueryType::Heading => "heading",
+            QueryType::Color => "color",
+            QueryType::Pensize => "pensize",
+        }
+        .to_string(),
+        Callee::Procedure(procedure) => procedure_name(procedure),
+    }
+}
+
+fn operation(operation: &BinaryOperation) -> &'static str {
+    match operation {
+        BinaryOperation::Calculation(cal) => match cal {
+            CalculationOperation::Plus => "+",
+            CalculationOperation::Dash => "-",
+            CalculationOperation::Star => "*",
+            CalculationOperation::Slash => "/",
+        },
+        BinaryOperation::Logic(logic) => match logic {
+            LogicalOperation::And => "and",
+            LogicalOperation::Or => "or",
+            LogicalOperation::Eq => "==",
+            LogicalOperation::Ne => "!=",
+            LogicalOperation::Lt => "<",
+            LogicalOperation::Gt => ">",
+        },
+    }
+}
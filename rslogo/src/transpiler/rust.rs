@@ -0,0 +1,727 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+    ast::{BinaryOperation, CallExpression, Callee, ProcedureDeclaration, Statement},
+    commands::Value,
+    font,
+    tokens::{
+        AssignmentType, CalculationOperation, CommandType, IdentifierType, LogicalOperation,
+        QueryType, ValueType,
+    },
+};
+
+use super::{procedure_declarations, Backend, SourceMap};
+
+const INDENT: &str = "    ";
+
+/// Values and turtle state of the generated program. The arithmetic, the
+/// conversions and `move_` copy `ValueType` and `Turtle` line for line, so
+/// the image is drawn by the same `unsvg` calls in the same order.
+const RUST_RUNTIME: &str = r#"#![allow(dead_code)]
+
+use unsvg::{Color, Image, COLORS};
+
+#[derive(Clone, Copy, Debug)]
+enum Value {
+    Float(f32),
+    Int(i32),
+    Bool(bool),
+}
+
+impl Value {
+    fn int(self) -> Option<i32> {
+        match self {
+            Value::Int(int) => Some(int),
+            Value::Float(float) if ((float as i32) as f32) == float => Some(float as i32),
+            _ => None,
+        }
+    }
+    fn float(self) -> Option<f32> {
+        match self {
+            Value::Float(float) => Some(float),
+            Value::Int(int) => Some(int as f32),
+            _ => None,
+        }
+    }
+    fn bool(self) -> bool {
+        match self {
+            Value::Bool(bool) => bool,
+            _ => panic!("Bool value is expected"),
+        }
+    }
+}
+
+fn add(left: Value, right: Value) -> Value {
+    match (left, right) {
+        (Value::Float(l), Value::Float(r)) => Value::Float(l + r),
+        (Value::Int(l), Value::Int(r)) => Value::Int(l + r),
+        (Value::Float(l), Value::Int(r)) => Value::Float(l + (r as f32)),
+        (Value::Int(l), Value::Float(r)) => Value::Float(l as f32 + r),
+        _ => panic!("Unsupported addition operation"),
+    }
+}
+
+fn sub(left: Value, right: Value) -> Value {
+    match (left, right) {
+        (Value::Int(l), Value::Int(r)) => Value::Int(l - r),
+        (Value::Float(l), Value::Float(r)) => Value::Float(l - r),
+        (Value::Int(l), Value::Float(r)) => Value::Float(l as f32 - r),
+        (Value::Float(l), Value::Int(r)) => Value::Float(l - r as f32),
+        _ => panic!("Unsupported subtraction operation"),
+    }
+}
+
+fn mul(left: Value, right: Value) -> Value {
+    match (left, right) {
+        (Value::Int(l), Value::Int(r)) => Value::Int(l * r),
+        (Value::Float(l), Value::Float(r)) => Value::Float(l * r),
+        (Value::Int(l), Value::Float(r)) => Value::Float(l as f32 * r),
+        (Value::Float(l), Value::Int(r)) => Value::Float(l * r as f32),
+        _ => panic!("Unsupported multiplication operation"),
+    }
+}
+
+fn div(left: Value, right: Value) -> Value {
+    match (left, right) {
+        (Value::Int(l), Value::Int(r)) => Value::Int(l / r),
+        (Value::Float(l), Value::Float(r)) => Value::Float(l / r),
+        (Value::Int(l), Value::Float(r)) => Value::Float(l as f32 / r),
+        (Value::Float(l), Value::Int(r)) => Value::Float(l / r as f32),
+        _ => panic!("Unsupported division operation"),
+    }
+}
+
+fn eq(left: Value, right: Value) -> bool {
+    match (left, right) {
+        (Value::Float(l), Value::Float(r)) => l == r,
+        (Value::Int(l), Value::Int(r)) => l == r,
+        (Value::Float(l), Value::Int(r)) => l == r as f32,
+        (Value::Int(l), Value::Float(r)) => l as f32 == r,
+        (Value::Bool(l), Value::Bool(r)) => l == r,
+        _ => panic!("Unsupported comparison operation"),
+    }
+}
+
+fn lt(left: Value, right: Value) -> bool {
+    match (left, right) {
+        (Value::Float(l), Value::Float(r)) => l < r,
+        (Value::Int(l), Value::Int(r)) => l < r,
+        (Value::Float(l), Value::Int(r)) => l < r as f32,
+        (Value::Int(l), Value::Float(r)) => (l as f32) < r,
+        _ => panic!("Unsupported comparison operation"),
+    }
+}
+
+fn gt(left: Value, right: Value) -> bool {
+    match (left, right) {
+        (Value::Float(l), Value::Float(r)) => l > r,
+        (Value::Int(l), Value::Int(r)) => l > r,
+        (Value::Float(l), Value::Int(r)) => l > r as f32,
+        (Value::Int(l), Value::Float(r)) => (l as f32) > r,
+        _ => panic!("Unsupported comparison operation"),
+    }
+}
+
+fn palette_index(color: i32) -> usize {
+    match usize::try_from(color) {
+        Ok(index) if index < 16 => index,
+        _ => panic!("Error: colour indices go from 0 to 15, got {}", color),
+    }
+}
+
+fn rgb(red: Value, green: Value, blue: Value) -> Color {
+    let channel = |value: Value| {
+        let value = value.int().expect("Expect an integer");
+        u8::try_from(value).unwrap_or_else(|_| {
+            panic!("Error: colour channels go from 0 to 255, got {}", value)
+        })
+    };
+    Color::new_rgb(channel(red), channel(green), channel(blue))
+}
+
+struct State {
+    vars: Vec<Option<Value>>,
+    image: Image,
+    pen: bool,
+    x: f32,
+    y: f32,
+    color: i32,
+    pen_color: Color,
+    palette: [Color; 16],
+    direction: i32,
+    pen_size: f32,
+    filling: bool,
+    label_height: f32,
+}
+
+impl State {
+    fn new(width: u32, height: u32) -> State {
+        State {
+            vars: vec![None; VARIABLES],
+            image: Image::new(width, height),
+            pen: false,
+            x: width as f32 / 2.0,
+            y: height as f32 / 2.0,
+            color: 7,
+            pen_color: COLORS[7],
+            palette: COLORS,
+            direction: 0,
+            pen_size: 1.0,
+            filling: false,
+            label_height: 12.0,
+        }
+    }
+    fn get(&self, slot: usize) -> Value {
+        self.vars[slot].expect("variable does not exist")
+    }
+    fn set(&mut self, slot: usize, value: Value) {
+        self.vars[slot] = Some(value);
+    }
+    fn add_assign(&mut self, slot: usize, value: Value) {
+        let current = self.vars[slot].take().expect("Variable does not exist");
+        self.vars[slot] = Some(add(current, value));
+    }
+    /// Binds parameters and returns the values they shadow.
+    fn bind(&mut self, params: &[usize], args: &[Value]) -> Vec<(usize, Option<Value>)> {
+        let mut saved: Vec<(usize, Option<Value>)> = Vec::new();
+        for param in params {
+            let value = self.vars[*param].take();
+            if !saved.iter().any(|(slot, _)| slot == param) {
+                saved.push((*param, value));
+            }
+        }
+        for (param, value) in params.iter().zip(args) {
+            self.vars[*param] = Some(*value);
+        }
+        saved
+    }
+    fn restore(&mut self, saved: Vec<(usize, Option<Value>)>) {
+        for (slot, value) in saved {
+            if value.is_some() {
+                self.vars[slot] = value;
+            }
+        }
+    }
+    fn move_(&mut self, distance: f32, direction_offset: i32) {
+        let color = self.pen_color;
+        let mut scratch = Image::new(10, 10);
+        let image = if self.pen { &mut self.image } else { &mut scratch };
+        let (x, y) = image
+            .draw_simple_line(self.x, self.y, self.direction + direction_offset, distance, color)
+            .unwrap_or_else(|err| panic!("Error: {:?}", err));
+        self.x = x;
+        self.y = y;
+    }
+    /// Chords two degrees wide, as `UnsvgCanvas` draws arcs.
+    fn arc(&mut self, angle: Value, radius: Value) {
+        let (Some(angle), Some(radius)) = (angle.float(), radius.float()) else {
+            return;
+        };
+        if !angle.is_finite() || !radius.is_finite() {
+            panic!("Error: Could not draw arc");
+        }
+        if !self.pen {
+            return;
+        }
+        let start = self.direction as f32;
+        let point = |degrees: f32| {
+            let radians = (start + degrees).to_radians();
+            (self.x + radius * radians.sin(), self.y - radius * radians.cos())
+        };
+        let sweep = angle.clamp(-360.0, 360.0);
+        let sign = sweep.signum();
+        let steps = (sweep.abs() / 2.0).ceil() as i32;
+        let mut lines = Vec::new();
+        for step in 0..steps {
+            let from = step as f32 * 2.0;
+            let to = (from + 2.0).min(sweep.abs());
+            let middle = start + sign * (from + to) / 2.0;
+            let length = 2.0 * radius * ((to - from) / 2.0).to_radians().sin();
+            lines.push((point(sign * from), (middle + sign * 90.0).round() as i32, length));
+        }
+        for ((x, y), direction, length) in lines {
+            self.image
+                .draw_simple_line(x, y, direction, length, self.pen_color)
+                .unwrap_or_else(|err| panic!("Error: {:?}", err));
+        }
+    }
+    fn circle(&mut self, radius: Value) {
+        self.arc(Value::Float(360.0), radius);
+    }
+    fn dot(&mut self) {
+        if self.pen {
+            self.image
+                .draw_simple_line(self.x - 0.5, self.y, 90, 1.0, self.pen_color)
+                .unwrap_or_else(|err| panic!("Error: {:?}", err));
+        }
+    }
+    fn setfillcolor(&mut self, color: Value) {
+        palette_index(color.int().expect("Expect an integer"));
+    }
+    fn setfillrgb(&mut self, red: Value, green: Value, blue: Value) {
+        rgb(red, green, blue);
+    }
+    fn beginfill(&mut self) {
+        self.filling = true;
+    }
+    fn endfill(&mut self) {
+        if !self.filling {
+            panic!("Error: ENDFILL without BEGINFILL");
+        }
+        self.filling = false;
+    }
+    fn penup(&mut self) {
+        self.pen = false;
+    }
+    fn pendown(&mut self) {
+        self.pen = true;
+    }
+    fn forward(&mut self, distance: Value) {
+        if let Some(distance) = distance.float() {
+            self.move_(distance, 0);
+        }
+    }
+    fn back(&mut self, distance: Value) {
+        if let Some(distance) = distance.float() {
+            self.move_(distance, 180);
+        }
+    }
+    fn left(&mut self, distance: Value) {
+        if let Some(distance) = distance.float() {
+            self.move_(distance, 270);
+        }
+    }
+    fn right(&mut self, distance: Value) {
+        if let Some(distance) = distance.float() {
+            self.move_(distance, 90);
+        }
+    }
+    fn setpencolor(&mut self, color: Value) {
+        let color = color.int().expect("Expect an integer");
+        self.pen_color = self.palette[palette_index(color)];
+        self.color = color;
+    }
+    fn setpenrgb(&mut self, red: Value, green: Value, blue: Value) {
+        self.pen_color = rgb(red, green, blue);
+        self.color = -1;
+    }
+    fn setpalette(&mut self, color: Value, red: Value, green: Value, blue: Value) {
+        let color = color.int().expect("Expect an integer");
+        self.palette[palette_index(color)] = rgb(red, green, blue);
+        if self.color == color {
+            self.pen_color = self.palette[palette_index(color)];
+        }
+    }
+    fn turn(&mut self, degrees: Value) {
+        self.direction += degrees.int().expect("Expect an integer");
+    }
+    fn setheading(&mut self, degrees: Value) {
+        self.direction = degrees.int().expect("Expect an integer");
+    }
+    fn setx(&mut self, x: Value) {
+        if let Some(x) = x.float() {
+            self.x = x;
+        }
+    }
+    fn sety(&mut self, y: Value) {
+        if let Some(y) = y.float() {
+            self.y = y;
+        }
+    }
+    fn setpensize(&mut self, size: Value) {
+        let size = size.float().expect("Expect a number");
+        if !(size.is_finite() && size >= 0.0) {
+            panic!("Error: Pen size must be a non-negative number, got {}", size);
+        }
+        self.pen_size = size;
+    }
+    fn setlabelheight(&mut self, height: Value) {
+        let height = height.float().expect("Expect a number");
+        if !(height.is_finite() && height > 0.0) {
+            panic!("Error: Label height must be a positive number, got {}", height);
+        }
+        self.label_height = height;
+    }
+    fn xcor(&self) -> Value {
+        Value::Float(self.x)
+    }
+    fn ycor(&self) -> Value {
+        Value::Float(self.y)
+    }
+    fn heading(&self) -> Value {
+        Value::Int(self.direction)
+    }
+    fn color(&self) -> Value {
+        Value::Int(self.color)
+    }
+    fn pensize(&self) -> Value {
+        Value::Float(self.pen_size)
+    }
+}
+"#;
+
+/// Added when the program has labels, after the `GLYPHS` it uses.
+const LABEL_RUNTIME: &str = r#"
+impl State {
+    /// Lines along the strokes of rslogo's font, as `UnsvgCanvas` draws them.
+    fn label(&mut self, text: &str) {
+        let unit = self.label_height / 9.0;
+        let (sin, cos) = (self.direction as f32).to_radians().sin_cos();
+        for (index, c) in text.chars().enumerate() {
+            let c = c.to_ascii_uppercase();
+            let strokes = GLYPHS.iter().find(|(glyph, _)| *glyph == c).map_or("", |glyph| glyph.1);
+            for stroke in strokes.split_whitespace() {
+                let points: Vec<(f32, f32)> = stroke
+                    .as_bytes()
+                    .chunks(2)
+                    .map(|point| {
+                        let x = (index as f32 * 5.0 + (point[0] - b'0') as f32) * unit;
+                        let y = ((point[1] - b'0') as f32 - 6.0) * unit;
+                        (self.x + x * cos - y * sin, self.y + x * sin + y * cos)
+                    })
+                    .collect();
+                for line in points.windows(2) {
+                    let (dx, dy) = (line[1].0 - line[0].0, line[1].1 - line[0].1);
+                    let direction = dx.atan2(-dy).to_degrees().round() as i32;
+                    self.image
+                        .draw_simple_line(line[0].0, line[0].1, direction, dx.hypot(dy), self.pen_color)
+                        .unwrap_or_else(|err| panic!("Error: {:?}", err));
+                }
+            }
+        }
+    }
+}
+"#;
+
+/// A standalone Rust program that draws through `unsvg::Image` and saves the
+/// SVG to the path given as its first argument. It needs `unsvg = "=1.1.2"`,
+/// since later releases take integer coordinates in `draw_simple_line`, and
+/// that version strokes every line one pixel wide whatever SETPENSIZE says and
+/// cannot fill, so fills are checked but not drawn. Labels are drawn in
+/// rslogo's stroke font, and only the glyphs they use are included.
+/// Procedures become functions, so unlike the interpreter, deep tail
+/// recursion uses stack space.
+pub struct RustBackend {}
+
+impl Backend for RustBackend {
+    fn emit(
+        &self,
+        statements: &[Statement],
+        width: u32,
+        height: u32,
+        map: &mut SourceMap,
+    ) -> String {
+        let mut generator = Generator {
+            slots: HashMap::new(),
+            names: Vec::new(),
+            procedures: HashMap::new(),
+            glyphs: BTreeSet::new(),
+        };
+        let mut declarations = Vec::new();
+        procedure_declarations(statements, &mut declarations);
+        for (index, procedure) in declarations.iter().enumerate() {
+            generator.procedures.insert(procedure.name().clone(), index);
+        }
+
+        // `main` and the functions are generated before the slot list that
+        // precedes them, so their mappings are shifted into place below.
+        let mut main = String::new();
+        let mut main_map = SourceMap::new();
+        generator.block(statements, 1, &mut main, &mut main_map);
+        let mut functions = String::new();
+        let mut functions_map = SourceMap::new();
+        for procedure in declarations {
+            let start = functions.len();
+            generator.procedure(procedure, &mut functions, &mut functions_map);
+            functions_map.record(procedure.span(), &functions, start);
+        }
+
+        let mut buf = String::from("// Generated by rslogo. Depends on unsvg = \"=1.1.2\".\n");
+        buf.push_str(RUST_RUNTIME);
+        buf.push_str(&format!(
+            "\n/// Variable slots: {}\nconst VARIABLES: usize = {};\n",
+            generator
+                .names
+                .iter()
+                .enumerate()
+                .map(|(slot, name)| format!("{} = {}", slot, name))
+                .collect::<Vec<String>>()
+                .join(", "),
+            generator.names.len()
+        ));
+        if !generator.glyphs.is_empty() {
+            buf.push_str(&format!(
+                "\nconst GLYPHS: [(char, &str); {}] = [\n",
+                generator.glyphs.len()
+            ));
+            for glyph in &generator.glyphs {
+                buf.push_str(&format!(
+                    "{}({:?}, {:?}),\n",
+                    INDENT,
+                    glyph,
+                    font::encoded(*glyph)
+                ));
+            }
+            buf.push_str("];\n");
+            buf.push_str(LABEL_RUNTIME);
+        }
+        map.extend(functions_map, buf.matches('\n').count());
+        buf.push_str(&functions);
+        buf.push_str("\nfn main() {\n");
+        buf.push_str(&format!(
+            "{}let s = &mut State::new({}, {});\n",
+            INDENT, width, height
+        ));
+        map.extend(main_map, buf.matches('\n').count());
+        buf.push_str(&main);
+        buf.push_str(&format!(
+            "{0}let path = std::env::args().nth(1).unwrap_or_else(|| \"output.svg\".to_string());\n\
+             {0}s.image.save_svg(&path).expect(\"Error saving svg\");\n}}\n",
+            INDENT
+        ));
+        buf
+    }
+}
+
+/// Numbers variables and procedures in the order they are first seen.
+struct Generator {
+    slots: HashMap<String, usize>,
+    names: Vec<String>,
+    procedures: HashMap<String, usize>,
+    /// The upper-cased characters labels use.
+    glyphs: BTreeSet<char>,
+}
+
+impl Generator {
+    fn slot(&mut self, name: &str) -> usize {
+        if let Some(slot) = self.slots.get(name) {
+            return *slot;
+        }
+        let slot = self.names.len();
+        self.slots.insert(name.to_string(), slot);
+        self.names.push(name.to_string());
+        slot
+    }
+
+    fn function(&self, name: &str) -> String {
+        let index = self
+            .procedures
+            .get(name)
+            .unwrap_or_else(|| panic!("Function {:?} is not found", name));
+        format!("procedure_{}", index)
+    }
+
+    fn procedure(
+        &mut self,
+        procedure: &ProcedureDeclaration,
+        buf: &mut String,
+        map: &mut SourceMap,
+    ) {
+        let params: Vec<String> = procedure
+            .arguments()
+            .iter()
+            .map(|argument| self.slot(argument).to_string())
+            .collect();
+        buf.push_str(&format!(
+            "\n/// TO {}\nfn {}(s: &mut State, args: &[Value]) {{\n",
+            procedure.name(),
+            self.function(procedure.name())
+        ));
+        buf.push_str(&format!(
+            "{}let saved = s.bind(&[{}], args);\n",
+            INDENT,
+            params.join(", ")
+        ));
+        self.block(procedure.body(), 1, buf, map);
+        buf.push_str(&format!("{}s.restore(saved);\n}}\n", INDENT));
+    }
+
+    fn block(
+        &mut self,
+        statements: &[Statement],
+        depth: usize,
+        buf: &mut String,
+        map: &mut SourceMap,
+    ) {
+        for statement in statements {
+            let start = buf.len();
+            self.statement(statement, depth, buf, map);
+            map.record(statement.span(), buf, start);
+        }
+    }
+
+    fn statement(
+        &mut self,
+        statement: &Statement,
+        depth: usize,
+        buf: &mut String,
+        map: &mut SourceMap,
+    ) {
+        let indent = INDENT.repeat(depth);
+        match statement {
+            Statement::CallExpression(call) => {
+                let call = self.call(call);
+                buf.push_str(&format!("{}{};\n", indent, call));
+            }
+            Statement::AssignmentExpression(assign) => {
+                let value = self.value(assign.right());
+                let slot = self.slot(assign.left());
+                let method = match assign.operation() {
+                    AssignmentType::Make => "set",
+                    AssignmentType::Addassign => "add_assign",
+                };
+                buf.push_str(&format!("{}s.{}({}, {});\n", indent, method, slot, value));
+            }
+            Statement::If(if_statement) => {
+                let test = self.condition(if_statement.test());
+                buf.push_str(&format!("{}if {} {{\n", indent, test));
+                self.block(if_statement.consequent(), depth + 1, buf, map);
+                buf.push_str(&format!("{}}}\n", indent));
+            }
+            Statement::While(while_statement) => {
+                let test = self.condition(while_statement.test());
+                buf.push_str(&format!("{}while {} {{\n", indent, test));
+                self.block(while_statement.body(), depth + 1, buf, map);
+                buf.push_str(&format!("{}}}\n", indent));
+            }
+            // Declared as a function up front.
+            Statement::ProcedureDeclaration(_) => {}
+        }
+    }
+
+    fn call(&mut self, call: &CallExpression) -> String {
+        let arguments: Vec<String> = call
+            .arguments()
+            .iter()
+            .map(|argument| self.value(argument))
+            .collect();
+        let arguments = arguments.join(", ");
+        match call.callee() {
+            Callee::Command(command) => {
+                let method = match command {
+                    CommandType::Penup => "penup",
+                    CommandType::Pendown => "pendown",
+                    CommandType::Forward => "forward",
+                    CommandType::Back => "back",
+                    CommandType::Left => "left",
+                    CommandType::Right => "right",
+                    CommandType::Setpencolor if call.arguments().len() == 3 => "setpenrgb",
+                    CommandType::Setpencolor => "setpencolor",
+                    CommandType::Turn => "turn",
+                    CommandType::Setheading => "setheading",
+                    CommandType::Setx => "setx",
+                    CommandType::Sety => "sety",
+                    CommandType::Setpensize => "setpensize",
+                    CommandType::Setpalette => "setpalette",
+                    CommandType::Arc => "arc",
+                    CommandType::Circle => "circle",
+                    CommandType::Dot => "dot",
+                    CommandType::Beginfill => "beginfill",
+                    CommandType::Endfill => "endfill",
+                    CommandType::Setfillcolor if call.arguments().len() == 3 => "setfillrgb",
+                    CommandType::Setfillcolor => "setfillcolor",
+                    CommandType::Label => "label",
+                    CommandType::Setlabelheight => "setlabelheight",
+                };
+                format!("s.{}({})", method, arguments)
+            }
+            Callee::Query(query) => {
+                let method = match query {
+                    QueryType::Xcor => "xcor",
+                    QueryType::Ycor => "ycor",
+                    QueryType::Heading => "heading",
+                    QueryType::Color => "color",
+                    QueryType::Pensize => "pensize",
+                };
+                format!("s.{}()", method)
+            }
+            // The arguments are evaluated before `s` is lent to the function.
+            Callee::Procedure(name) => format!(
+                "{{ let args = [{}]; {}(s, &args) }}",
+                arguments,
+                self.function(name)
+            ),
+        }
+    }
+
+    /// An expression of type `Value`, or the `&str` LABEL takes.
+    fn value(&mut self, value: &Value) -> String {
+        match value {
+            Value::Word(word) => {
+                self.glyphs
+                    .extend(word.chars().map(|c| c.to_ascii_uppercase()));
+                format!("{:?}", word)
+            }
+            Value::Literal(ValueType::Int(number)) => format!("Value::Int({})", number),
+            Value::Literal(ValueType::Float(number)) => format!("Value::Float({:?})", number),
+            Value::Literal(ValueType::Bool(boolean)) => format!("Value::Bool({})", boolean),
+            Value::Identifier(IdentifierType::Variable(name)) => {
+                format!("s.get({})", self.slot(name))
+            }
+            Value::Variable(variable) => format!("s.get({})", self.slot(variable.name())),
+            Value::Identifier(IdentifierType::Procedure(name)) => {
+                panic!("Procedure {} can not be used as a value", name)
+            }
+            Value::Statement(call) => match call.callee() {
+                Callee::Procedure(name) => {
+                    panic!("Procedure {} does not return a value", name)
+                }
+                _ => self.call(call),
+            },
+            Value::BinaryExpression(binary) => match binary.operation() {
+                BinaryOperation::Calculation(calculation) => {
+                    let left = self.value(binary.left());
+                    let right = self.value(binary.right());
+                    let function = match calculation {
+                        CalculationOperation::Plus => "add",
+                        CalculationOperation::Dash => "sub",
+                        CalculationOperation::Star => "mul",
+                        CalculationOperation::Slash => "div",
+                    };
+                    format!("{}({}, {})", function, left, right)
+                }
+                BinaryOperation::Logic(_) => format!("Value::Bool({})", self.test(value)),
+            },
+        }
+    }
+
+    /// A test without the parentheses `if` and `while` do not need.
+    fn condition(&mut self, test: &Value) -> String {
+        let text = self.test(test);
+        match test {
+            Value::BinaryExpression(_) if text.starts_with('(') => {
+                text[1..text.len() - 1].to_string()
+            }
+            _ => text,
+        }
+    }
+
+    /// An expression of type `bool`.
+    fn test(&mut self, value: &Value) -> String {
+        let Value::BinaryExpression(binary) = value else {
+            return format!("{}.bool()", self.value(value));
+        };
+        let BinaryOperation::Logic(logic) = binary.operation() else {
+            return format!("{}.bool()", self.value(value));
+        };
+        let function = match logic {
+            // Both sides are evaluated, as they are in the interpreter.
+            LogicalOperation::And | LogicalOperation::Or => {
+                let left = self.test(binary.left());
+                let right = self.test(binary.right());
+                let operator = match logic {
+                    LogicalOperation::And => "&",
+                    _ => "|",
+                };
+                return format!("({} {} {})", left, operator, right);
+            }
+            LogicalOperation::Eq => "eq",
+            LogicalOperation::Ne => "!eq",
+            LogicalOperation::Lt => "lt",
+            LogicalOperation::Gt => "gt",
+        };
+        let left = self.value(binary.left());
+        let right = self.value(binary.right());
+        format!("{}({}, {})", function, left, right)
+    }
+}
@@ -0,0 +1,171 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::ast::Span;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// How `--source-map` writes the mappings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SourceMapFormat {
+    /// One JSON object per statement
+    #[default]
+    Jsonl,
+    /// Source Map revision 3, as browsers read for JavaScript
+    V3,
+}
+
+/// Generated lines `generated_start..=generated_end` came from the statement
+/// on source lines `source_start..=source_end`. All lines count from 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Mapping {
+    generated_start: usize,
+    generated_end: usize,
+    source_start: usize,
+    source_end: usize,
+}
+
+#[allow(dead_code)]
+impl Mapping {
+    pub fn generated_start(&self) -> usize {
+        self.generated_start
+    }
+    pub fn generated_end(&self) -> usize {
+        self.generated_end
+    }
+    pub fn source_start(&self) -> usize {
+        self.source_start
+    }
+    pub fn source_end(&self) -> usize {
+        self.source_end
+    }
+}
+
+/// Links generated lines back to statements. Every statement gets a mapping,
+/// so the mappings of a block's statements nest inside the block's own.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    mappings: Vec<Mapping>,
+}
+
+#[allow(dead_code)]
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap::default()
+    }
+
+    /// Records that what `buf` gained since byte `start`, less any leading
+    /// blank lines, came from `span`. Statements from an AST without spans,
+    /// and statements that generated nothing, are left out.
+    pub fn record(&mut self, span: Span, buf: &str, start: usize) {
+        let start = buf.len() - buf[start..].trim_start_matches('\n').len();
+        if span.start() == 0 || start == buf.len() {
+            return;
+        }
+        let generated_start = buf[..start].matches('\n').count() + 1;
+        let generated_end =
+            generated_start + buf[start..].trim_end_matches('\n').matches('\n').count();
+        self.mappings.push(Mapping {
+            generated_start,
+            generated_end,
+            source_start: span.start(),
+            source_end: span.end(),
+        });
+    }
+
+    /// Adds mappings recorded against a buffer that was then placed after
+    /// `lines` lines of output.
+    pub fn extend(&mut self, other: SourceMap, lines: usize) {
+        self.mappings
+            .extend(other.mappings.into_iter().map(|mapping| Mapping {
+                generated_start: mapping.generated_start + lines,
+                generated_end: mapping.generated_end + lines,
+                ..mapping
+            }));
+    }
+
+    /// Ordered by generated line, with enclosing statements first.
+    pub fn mappings(&self) -> Vec<Mapping> {
+        let mut mappings = self.mappings.clone();
+        mappings
+            .sort_by_key(|mapping| (mapping.generated_start, usize::MAX - mapping.generated_end));
+        mappings
+    }
+
+    pub fn write(&self, format: SourceMapFormat, source: &str) -> String {
+        match format {
+            SourceMapFormat::Jsonl => self.to_json_lines(),
+            SourceMapFormat::V3 => self.to_v3(source),
+        }
+    }
+
+    pub fn to_json_lines(&self) -> String {
+        self.mappings()
+            .iter()
+            .map(|mapping| {
+                serde_json::to_string(mapping).expect("Mappings always serialize") + "\n"
+            })
+            .collect()
+    }
+
+    /// Each generated line maps to the first line of the innermost statement
+    /// that produced it.
+    pub fn to_v3(&self, source: &str) -> String {
+        let lines = self
+            .mappings
+            .iter()
+            .map(|mapping| mapping.generated_end)
+            .max()
+            .unwrap_or(0);
+        let mut segments = Vec::new();
+        let mut previous = 0;
+        for line in 1..=lines {
+            let innermost = self
+                .mappings
+                .iter()
+                .filter(|mapping| (mapping.generated_start..=mapping.generated_end).contains(&line))
+                .min_by_key(|mapping| mapping.generated_end - mapping.generated_start);
+            match innermost {
+                Some(mapping) => {
+                    let source_line = mapping.source_start as i64 - 1;
+                    let mut segment = String::new();
+                    for field in [0, 0, source_line - previous, 0] {
+                        vlq(field, &mut segment);
+                    }
+                    previous = source_line;
+                    segments.push(segment);
+                }
+                None => segments.push(String::new()),
+            }
+        }
+        json!({
+            "version": 3,
+            "sources": [source],
+            "names": [],
+            "mappings": segments.join(";"),
+        })
+        .to_string()
+    }
+}
+
+/// Appends a Base64 VLQ field: the sign in the lowest bit, then five bits a
+/// digit with a continuation bit.
+fn vlq(value: i64, buf: &mut String) {
+    let mut rest = if value < 0 {
+        ((-value) << 1) | 1
+    } else {
+        value << 1
+    };
+    loop {
+        let mut digit = rest & 0b11111;
+        rest >>= 5;
+        if rest > 0 {
+            digit |= 0b100000;
+        }
+        buf.push(BASE64[digit as usize] as char);
+        if rest == 0 {
+            break;
+        }
+    }
+}
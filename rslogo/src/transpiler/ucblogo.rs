@@ -0,0 +1,318 @@
+use crate::{
+    ast::{BinaryOperation, CallExpression, Callee, ProcedureDeclaration, Statement},
+    commands::Value,
+    tokens::{
+        AssignmentType, CalculationOperation, CommandType, IdentifierType, LogicalOperation,
+        QueryType, ValueType,
+    },
+};
+
+use super::{procedure_declarations, Backend, SourceMap};
+
+const INDENT: &str = "  ";
+
+/// UCBLogo numbers colours 2 and 3 the other way round from `unsvg`. The
+/// permutation is its own inverse, so it maps both ways.
+const PALETTE: [i32; 16] = [0, 1, 3, 2, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+/// Standard Logo for UCBLogo and FMSLogo. Numbers are unquoted, expressions
+/// are infix, and procedures are all defined before the program runs. The
+/// turtle keeps rslogo's behaviour: LEFT and RIGHT strafe, SETX and SETY
+/// never draw, and coordinates are measured from the top-left corner with y
+/// growing down. Division always returns a decimal in UCBLogo, so `/` on two
+/// integers no longer truncates, and HEADING stays between 0 and 360.
+/// UCBLogo channels go to 100 rather than 255, and it only lets SETPALETTE
+/// redefine colours from 8 up. Fills have no counterpart and are left out,
+/// and labels are written level whatever the heading.
+pub struct UcbLogoBackend {}
+
+impl Backend for UcbLogoBackend {
+    fn emit(
+        &self,
+        statements: &[Statement],
+        width: u32,
+        height: u32,
+        map: &mut SourceMap,
+    ) -> String {
+        let writer = Writer {
+            width: width as f32,
+            height: height as f32,
+        };
+        let mut buf = format!(
+            "; Translated by rslogo for a {}x{} canvas\nCLEARSCREEN\nSETBACKGROUND 0\nSETPC 7\nPENUP\n",
+            width, height
+        );
+        let mut declarations = Vec::new();
+        procedure_declarations(statements, &mut declarations);
+        for procedure in declarations {
+            buf.push('\n');
+            let start = buf.len();
+            writer.procedure(procedure, &mut buf, map);
+            map.record(procedure.span(), &buf, start);
+        }
+        buf.push('\n');
+        writer.block(statements, 0, &mut buf, map);
+        buf
+    }
+}
+
+/// Carries the canvas size that turtle coordinates are translated with.
+struct Writer {
+    width: f32,
+    height: f32,
+}
+
+impl Writer {
+    fn procedure(&self, procedure: &ProcedureDeclaration, buf: &mut String, map: &mut SourceMap) {
+        let mut header = format!("TO {}", procedure.name());
+        for argument in procedure.arguments() {
+            header.push_str(&format!(" :{}", argument));
+        }
+        buf.push_str(&header);
+        buf.push('\n');
+        self.block(procedure.body(), 1, buf, map);
+        buf.push_str("END\n");
+    }
+
+    fn block(&self, statements: &[Statement], depth: usize, buf: &mut String, map: &mut SourceMap) {
+        for statement in statements {
+            let start = buf.len();
+            self.statement(statement, depth, buf, map);
+            map.record(statement.span(), buf, start);
+        }
+    }
+
+    fn statement(
+        &self,
+        statement: &Statement,
+        depth: usize,
+        buf: &mut String,
+        map: &mut SourceMap,
+    ) {
+        let indent = INDENT.repeat(depth);
+        match statement {
+            Statement::CallExpression(call) => {
+                buf.push_str(&format!("{}{}\n", indent, self.command(call)))
+            }
+            Statement::AssignmentExpression(assign) => {
+                let name = assign.left();
+                let right = self.value(assign.right());
+                let value = match assign.operation() {
+                    AssignmentType::Make => unwrap(&right),
+                    AssignmentType::Addassign => format!(":{} + {}", name, right),
+                };
+                buf.push_str(&format!("{}MAKE \"{} {}\n", indent, name, value));
+            }
+            Statement::If(if_statement) => {
+                let test = self.value(if_statement.test());
+                buf.push_str(&format!("{}IF {} [\n", indent, unwrap(&test)));
+                self.block(if_statement.consequent(), depth + 1, buf, map);
+                buf.push_str(&format!("{}]\n", indent));
+            }
+            Statement::While(while_statement) => {
+                let test = self.value(while_statement.test());
+                buf.push_str(&format!("{}WHILE [{}] [\n", indent, unwrap(&test)));
+                self.block(while_statement.body(), depth + 1, buf, map);
+                buf.push_str(&format!("{}]\n", indent));
+            }
+            // Defined at the top of the program.
+            Statement::ProcedureDeclaration(_) => {}
+        }
+    }
+
+    fn command(&self, call: &CallExpression) -> String {
+        let arguments: Vec<String> = call
+            .arguments()
+            .iter()
+            .map(|argument| self.value(argument))
+            .collect();
+        let command = match call.callee() {
+            Callee::Command(command) => command,
+            Callee::Query(_) => return self.value(&Value::Statement(Box::new(call.clone()))),
+            Callee::Procedure(name) => {
+                let mut line = name.clone();
+                for argument in arguments {
+                    line.push(' ');
+                    line.push_str(&argument);
+                }
+                return line;
+            }
+        };
+        let argument = arguments.join(" ");
+        match command {
+            CommandType::Penup => "PENUP".to_string(),
+            CommandType::Pendown => "PENDOWN".to_string(),
+            CommandType::Forward => format!("FORWARD {}", argument),
+            CommandType::Back => format!("BACK {}", argument),
+            CommandType::Left => format!("LEFT 90 FORWARD {} RIGHT 90", argument),
+            CommandType::Right => format!("RIGHT 90 FORWARD {} LEFT 90", argument),
+            CommandType::Setpencolor if call.arguments().len() == 3 => {
+                format!("SETPC {}", self.rgb(call.arguments()))
+            }
+            CommandType::Setpalette => {
+                let color = match call.arguments().first() {
+                    Some(Value::Literal(ValueType::Int(color))) if (0..16).contains(color) => {
+                        PALETTE[*color as usize].to_string()
+                    }
+                    _ => palette(&arguments[0]),
+                };
+                format!("SETPALETTE {} {}", color, self.rgb(&call.arguments()[1..]))
+            }
+            CommandType::Setpencolor => match call.arguments().first() {
+                Some(Value::Literal(ValueType::Int(color))) if (0..16).contains(color) => {
+                    format!("SETPC {}", PALETTE[*color as usize])
+                }
+                _ => format!("SETPC {}", palette(&argument)),
+            },
+            CommandType::Turn => format!("RIGHT {}", argument),
+            CommandType::Setheading => format!("SETH {}", argument),
+            CommandType::Setx => {
+                let x = self.offset(call.arguments().first(), -self.width / 2.0, 1.0);
+                jump(&format!("SETX {}", x))
+            }
+            CommandType::Sety => {
+                let y = self.offset(call.arguments().first(), self.height / 2.0, -1.0);
+                jump(&format!("SETY {}", y))
+            }
+            CommandType::Setpensize => format!("SETPENSIZE {}", argument),
+            CommandType::Arc => format!("ARC {}", argument),
+            CommandType::Circle => format!("CIRCLE {}", argument),
+            CommandType::Dot => "IF PENDOWNP [DOT POS]".to_string(),
+            CommandType::Label => format!("LABEL {}", argument),
+            CommandType::Setlabelheight => format!("SETLABELHEIGHT {}", argument),
+            CommandType::Beginfill | CommandType::Endfill | CommandType::Setfillcolor => {
+                format!("; {} is not supported by UCBLogo", command.keyword())
+            }
+        }
+    }
+
+    /// A colour list with the channels scaled from 255 to 100.
+    fn rgb(&self, channels: &[Value]) -> String {
+        let channels: Vec<String> = channels
+            .iter()
+            .map(|channel| match channel {
+                Value::Literal(ValueType::Int(number)) => {
+                    number_text(*number as f32 * 100.0 / 255.0)
+                }
+                channel => format!("({} * 100 / 255)", self.value(channel)),
+            })
+            .collect();
+        if channels
+            .iter()
+            .all(|channel| channel.parse::<f32>().is_ok())
+        {
+            format!("[{}]", channels.join(" "))
+        } else {
+            format!("(LIST {})", channels.join(" "))
+        }
+    }
+
+    /// `scale * value + offset`, folded when the value is a number.
+    fn offset(&self, value: Option<&Value>, offset: f32, scale: f32) -> String {
+        match value {
+            Some(Value::Literal(ValueType::Int(number))) => {
+                number_text(scale * *number as f32 + offset)
+            }
+            Some(Value::Literal(ValueType::Float(number))) => number_text(scale * number + offset),
+            Some(value) => {
+                let value = self.value(value);
+                if scale < 0.0 {
+                    format!("({} - {})", number_text(offset), value)
+                } else {
+                    format!("({} - {})", value, number_text(-offset))
+                }
+            }
+            None => String::new(),
+        }
+    }
+
+    fn value(&self, value: &Value) -> String {
+        match value {
+            Value::Literal(ValueType::Bool(boolean)) => format!("\"{}", boolean),
+            Value::Literal(literal) => literal.to_string(),
+            Value::Identifier(IdentifierType::Variable(name)) => format!(":{}", name),
+            Value::Identifier(IdentifierType::Procedure(name)) => name.clone(),
+            Value::Variable(variable) => format!(":{}", variable.name()),
+            Value::Word(word) if !word.is_empty() && !word.contains(' ') => format!("\"{}", word),
+            Value::Word(words) => format!("[{}]", words),
+            Value::Statement(call) => match call.callee() {
+                Callee::Query(QueryType::Xcor) => {
+                    format!("(XCOR + {})", number_text(self.width / 2.0))
+                }
+                Callee::Query(QueryType::Ycor) => {
+                    format!("({} - YCOR)", number_text(self.height / 2.0))
+                }
+                Callee::Query(QueryType::Heading) => "HEADING".to_string(),
+                Callee::Query(QueryType::Color) => palette("PENCOLOR"),
+                // UCBLogo reports the pen size as a [width height] list.
+                Callee::Query(QueryType::Pensize) => "(FIRST PENSIZE)".to_string(),
+                _ => format!("({})", self.command(call)),
+            },
+            Value::BinaryExpression(binary) => {
+                let left = self.value(binary.left());
+                let right = self.value(binary.right());
+                let operation = match binary.operation() {
+                    BinaryOperation::Logic(LogicalOperation::And) => {
+                        return format!("(AND {} {})", left, right)
+                    }
+                    BinaryOperation::Logic(LogicalOperation::Or) => {
+                        return format!("(OR {} {})", left, right)
+                    }
+                    BinaryOperation::Logic(logic) => match logic {
+                        LogicalOperation::Eq => "=",
+                        LogicalOperation::Ne => "<>",
+                        LogicalOperation::Lt => "<",
+                        LogicalOperation::Gt => ">",
+                        LogicalOperation::And | LogicalOperation::Or => unreachable!(),
+                    },
+                    BinaryOperation::Calculation(calculation) => match calculation {
+                        CalculationOperation::Plus => "+",
+                        CalculationOperation::Dash => "-",
+                        CalculationOperation::Star => "*",
+                        CalculationOperation::Slash => "/",
+                    },
+                };
+                format!("({} {} {})", left, operation, right)
+            }
+        }
+    }
+}
+
+/// Runs a SETX or SETY with the pen up, as rslogo never draws for them.
+fn jump(command: &str) -> String {
+    format!("IFELSE PENDOWNP [PENUP {} PENDOWN] [{}]", command, command)
+}
+
+/// Looks a colour index up in the other palette.
+fn palette(color: &str) -> String {
+    let palette: Vec<String> = PALETTE.iter().map(|color| color.to_string()).collect();
+    format!("(ITEM {} + 1 [{}])", unwrap(color), palette.join(" "))
+}
+
+fn number_text(number: f32) -> String {
+    ValueType::Float(number).to_string()
+}
+
+/// An expression without the parentheses its context does not need.
+fn unwrap(text: &str) -> String {
+    match text
+        .strip_prefix('(')
+        .and_then(|text| text.strip_suffix(')'))
+    {
+        Some(inner) if balanced(inner) => inner.to_string(),
+        _ => text.to_string(),
+    }
+}
+
+fn balanced(text: &str) -> bool {
+    let mut depth = 0;
+    for char in text.chars() {
+        match char {
+            '(' => depth += 1,
+            ')' if depth == 0 => return false,
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    depth == 0
+}
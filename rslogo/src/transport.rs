@@ -0,0 +1,53 @@
+use std::io::{self, BufRead, Write};
+
+use serde_json::Value as Json;
+
+/// Reads and writes JSON messages framed by a `Content-Length` header, as
+/// both the Debug Adapter and the Language Server protocols do.
+pub struct Transport<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Transport<R, W> {
+    pub fn new(input: R, output: W) -> Transport<R, W> {
+        Transport { input, output }
+    }
+
+    /// Returns `None` once the client closes the stream.
+    pub fn receive(&mut self) -> io::Result<Option<Json>> {
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            if self.input.read_line(&mut header)? == 0 {
+                return Ok(None);
+            }
+            let header = header.trim();
+            if header.is_empty() {
+                if length.is_some() {
+                    break;
+                }
+                continue;
+            }
+            if let Some(value) = header.strip_prefix("Content-Length:") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+        let mut body = vec![0; length.unwrap_or_default()];
+        self.input.read_exact(&mut body)?;
+        serde_json::from_slice(&body)
+            .map(Some)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn send(&mut self, message: &Json) -> io::Result<()> {
+        let body = message.to_string();
+        write!(
+            self.output,
+            "Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+        self.output.flush()
+    }
+}
@@ -1,37 +1,62 @@
 use unsvg::{Image, COLORS};
+
+use crate::{
+    canvas::{Canvas, Line, UnsvgCanvas},
+    display_list::{Arc, DisplayList, Dot, Fill, Label, Primitive, Rgb, Segment},
+};
+
+/// unsvg strokes every line one pixel wide.
+pub const DEFAULT_PEN_SIZE: f32 = 1.0;
+pub const DEFAULT_LABEL_HEIGHT: f32 = 12.0;
+
 pub struct Turtle {
     pen: bool,
     x: f32,
     y: f32,
+    /// The palette index of the pen colour, or -1 for an RGB colour.
     color: i32,
+    pen_color: Rgb,
+    palette: [Rgb; 16],
     direction: i32,
-    map: Option<Image>,
+    pen_size: f32,
+    fill_color: Rgb,
+    /// Where BEGINFILL was in the display list, and the points visited since.
+    filling: Option<(usize, Vec<(f32, f32)>)>,
+    label_height: f32,
+    canvas: Box<dyn Canvas>,
+    display_list: DisplayList,
 }
 
 #[allow(dead_code)]
 impl Turtle {
-    pub fn new(x: f32, y: f32) -> Turtle {
+    /// Starts in the middle of the canvas, facing up with the pen raised.
+    pub fn new(canvas: Box<dyn Canvas>) -> Turtle {
+        let (width, height) = canvas.dimensions();
         Turtle {
             pen: false,
-            x,
-            y,
+            x: width as f32 / 2.0,
+            y: height as f32 / 2.0,
             color: 7,
+            pen_color: COLORS[7].into(),
+            palette: COLORS.map(Rgb::from),
             direction: 0,
-            map: None,
+            pen_size: DEFAULT_PEN_SIZE,
+            fill_color: COLORS[7].into(),
+            filling: None,
+            label_height: DEFAULT_LABEL_HEIGHT,
+            canvas,
+            display_list: DisplayList::new(width, height),
         }
     }
     pub fn new_with_img(image: Image) -> Turtle {
-        let (width, height) = image.get_dimensions();
-        let mut turtle = Turtle::new(width as f32 / 2.0, height as f32 / 2.0);
-        turtle.attach_img(image);
-        turtle
+        Turtle::new(Box::new(UnsvgCanvas::new(image)))
     }
     pub fn pen(&self) -> bool {
         // println!("PEN: -> {}", self.pen);
         self.pen
     }
-    pub fn attach_img(&mut self, map: Image) {
-        self.map = Some(map);
+    pub fn attach_canvas(&mut self, canvas: Box<dyn Canvas>) {
+        self.canvas = canvas;
     }
     pub fn x(&self) -> f32 {
         // println!("XCOR: -> {}", self.x);
@@ -49,6 +74,9 @@ impl Turtle {
         // println!("COLOR -> {}", self.color);
         self.color
     }
+    pub fn pen_size(&self) -> f32 {
+        self.pen_size
+    }
     pub fn pen_up(&mut self) {
         // println!("PENUP");
         self.pen = false;
@@ -57,17 +85,99 @@ impl Turtle {
         // println!("PENDOWN");
         self.pen = true;
     }
+    pub fn pen_color(&self) -> Rgb {
+        self.pen_color
+    }
+    pub fn palette(&self) -> &[Rgb; 16] {
+        &self.palette
+    }
     pub fn set_color(&mut self, color: i32) {
         // println!("SETCOLOR: {}", color);
+        self.pen_color = self.palette[Turtle::palette_index(color)];
         self.color = color;
     }
+    pub fn set_rgb(&mut self, rgb: Rgb) {
+        self.pen_color = rgb;
+        self.color = -1;
+    }
+    /// Redefines a palette entry. A pen already using it changes too.
+    pub fn set_palette(&mut self, color: i32, rgb: Rgb) {
+        self.palette[Turtle::palette_index(color)] = rgb;
+        if self.color == color {
+            self.pen_color = rgb;
+        }
+    }
+    pub fn fill_color(&self) -> Rgb {
+        self.fill_color
+    }
+    pub fn set_fill_color(&mut self, color: i32) {
+        self.fill_color = self.palette[Turtle::palette_index(color)];
+    }
+    pub fn set_fill_rgb(&mut self, rgb: Rgb) {
+        self.fill_color = rgb;
+    }
+    /// Starts recording the points the turtle visits, from where it is.
+    pub fn begin_fill(&mut self) {
+        self.filling = Some((self.display_list.primitives().len(), vec![(self.x, self.y)]));
+    }
+    /// Fills the points visited since BEGINFILL. The fill goes under
+    /// whatever was drawn in between, so outlines stay on top.
+    pub fn end_fill(&mut self) {
+        let (index, points) = self
+            .filling
+            .take()
+            .expect("Error: ENDFILL without BEGINFILL");
+        if points.len() < 3 {
+            return;
+        }
+        let fill = Fill::new(points, self.fill_color);
+        self.canvas
+            .fill(&fill)
+            .unwrap_or_else(|err| panic!("Error: {}", err));
+        self.display_list.insert(index, Primitive::Fill(fill));
+    }
+    fn visit(&mut self) {
+        if let Some((_, points)) = &mut self.filling {
+            points.push((self.x, self.y));
+        }
+    }
+    fn palette_index(color: i32) -> usize {
+        match usize::try_from(color) {
+            Ok(index) if index < 16 => index,
+            _ => panic!("Error: colour indices go from 0 to 15, got {}", color),
+        }
+    }
+    /// The stroke width of later lines, in pixels.
+    pub fn set_pen_size(&mut self, size: f32) {
+        if !(size.is_finite() && size >= 0.0) {
+            panic!(
+                "Error: Pen size must be a non-negative number, got {}",
+                size
+            );
+        }
+        self.pen_size = size;
+    }
+    pub fn label_height(&self) -> f32 {
+        self.label_height
+    }
+    pub fn set_label_height(&mut self, height: f32) {
+        if !(height.is_finite() && height > 0.0) {
+            panic!(
+                "Error: Label height must be a positive number, got {}",
+                height
+            );
+        }
+        self.label_height = height;
+    }
     pub fn set_x(&mut self, x: f32) {
         // println!("SETX: {}", x);
         self.x = x;
+        self.visit();
     }
     pub fn set_y(&mut self, y: f32) {
         // println!("SETY: {}", y);
         self.y = y;
+        self.visit();
     }
     pub fn turn(&mut self, degrees: i32) {
         // println!("TURN: {}", degrees);
@@ -96,38 +206,90 @@ impl Turtle {
     }
 
     fn move_(&mut self, distance: f32, direction_offset: i32) {
-        let color = *COLORS.get(self.color as usize).expect("color is not valid");
+        let color = self.pen_color.into();
+        let line = Line::new(
+            (self.x, self.y),
+            self.direction + direction_offset,
+            distance,
+            color,
+        );
+        let (x, y) = line.to();
         if self.pen {
-            let map = self.map.as_mut().expect("there is no image was given");
-            let (x, y) = map
-                .draw_simple_line(
-                    self.x,
-                    self.y,
-                    self.direction + direction_offset,
-                    distance,
-                    color,
-                )
-                .unwrap_or_else(|err| panic!("Error: {:?}", err));
-            // println!("{} ->: {}, {} ->y: {}", self.x, x, self.y, y);
-            self.x = x;
-            self.y = y;
-        } else {
-            let mut map = Image::new(10, 10);
-            let (x, y) = map
-                .draw_simple_line(
-                    self.x,
-                    self.y,
-                    self.direction + direction_offset,
-                    distance,
-                    color,
-                )
-                .unwrap_or_else(|err| panic!("Error: {:?}", err));
-            // println!("{} ->: {}, {} ->y: {}", self.x, x, self.y, y);
-            self.x = x;
-            self.y = y;
-        };
+            self.canvas
+                .line(&line)
+                .unwrap_or_else(|err| panic!("Error: {}", err));
+            self.display_list.push(Primitive::Segment(Segment::new(
+                line.from(),
+                line.to(),
+                self.pen_color,
+                self.pen_size,
+            )));
+        } else if !x.is_finite() || !y.is_finite() {
+            panic!("Error: Could not draw line");
+        }
+        // println!("{} ->: {}, {} ->y: {}", self.x, x, self.y, y);
+        self.x = x;
+        self.y = y;
+        self.visit();
+    }
+    /// Draws `angle` degrees of a circle around the turtle, clockwise from
+    /// its heading, as UCBLogo does. The turtle stays where it is.
+    pub fn arc(&mut self, angle: f32, radius: f32) {
+        if !angle.is_finite() || !radius.is_finite() {
+            panic!("Error: Could not draw arc");
+        }
+        if !self.pen {
+            return;
+        }
+        let arc = Arc::new(
+            (self.x, self.y),
+            radius,
+            self.direction as f32,
+            angle,
+            self.pen_color,
+            self.pen_size,
+        );
+        self.canvas
+            .arc(&arc)
+            .unwrap_or_else(|err| panic!("Error: {}", err));
+        self.display_list.push(Primitive::Arc(arc));
+    }
+    pub fn circle(&mut self, radius: f32) {
+        self.arc(360.0, radius);
+    }
+    /// Marks the turtle's position with a dot as wide as the pen.
+    pub fn dot(&mut self) {
+        if !self.pen {
+            return;
+        }
+        let dot = Dot::new((self.x, self.y), self.pen_size, self.pen_color);
+        self.canvas
+            .dot(&dot)
+            .unwrap_or_else(|err| panic!("Error: {}", err));
+        self.display_list.push(Primitive::Dot(dot));
+    }
+    /// Writes `text` from the turtle's position in the pen colour, whether or
+    /// not the pen is down, as UCBLogo does.
+    pub fn label(&mut self, text: &str) {
+        let label = Label::new(
+            (self.x, self.y),
+            self.direction as f32,
+            self.label_height,
+            text.to_string(),
+            self.pen_color,
+        );
+        self.canvas
+            .label(&label)
+            .unwrap_or_else(|err| panic!("Error: {}", err));
+        self.display_list.push(Primitive::Label(label));
+    }
+    pub fn display_list(&self) -> &DisplayList {
+        &self.display_list
+    }
+    pub fn canvas(&self) -> &dyn Canvas {
+        self.canvas.as_ref()
     }
     pub fn image(&self) -> &Image {
-        self.map.as_ref().expect("there is no image was given")
+        self.canvas.image().expect("there is no image was given")
     }
 }